@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+/// Command line arguments split into positionals, `--flag`s and `--option value` / `--option=value` pairs.
+/// Only names listed as taking a value consume the argument after them.
+#[derive(Debug, Default)]
+pub struct Args {
+  positional: Vec<String>,
+  flags: Vec<String>,
+  options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+  pub fn parse(args: impl IntoIterator<Item = String>, takes_value: &[&str]) -> Result<Self, String> {
+    let mut parsed = Self::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+      if arg == "--" {
+        parsed.positional.extend(args.by_ref());
+        break;
+      }
+
+      let Some(name) = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-').filter(|n| !n.is_empty()))
+      else {
+        parsed.positional.push(arg);
+        continue;
+      };
+
+      if let Some((name, value)) = name.split_once('=') {
+        parsed.options.entry(name.to_string()).or_default().push(value.to_string());
+      } else if takes_value.contains(&name) {
+        let value = args.next().ok_or_else(|| format!("option '{}' needs a value", arg))?;
+        parsed.options.entry(name.to_string()).or_default().push(value);
+      } else {
+        parsed.flags.push(name.to_string());
+      }
+    }
+
+    Ok(parsed)
+  }
+
+  pub fn positional(&self) -> &[String] {
+    &self.positional
+  }
+
+  pub fn first(&self) -> Option<&str> {
+    self.positional.first().map(String::as_str)
+  }
+
+  pub fn flag(&self, names: &[&str]) -> bool {
+    self.flags.iter().any(|f| names.contains(&f.as_str()))
+  }
+
+  pub fn value(&self, name: &str) -> Option<&str> {
+    self.options.get(name).and_then(|v| v.last()).map(String::as_str)
+  }
+
+  /// Every value of a repeatable option, with comma separated lists split up.
+  pub fn values(&self, name: &str) -> Vec<&str> {
+    let values = self.options.get(name).map(Vec::as_slice).unwrap_or_default();
+    values.iter().flat_map(|v| v.split(',')).filter(|v| !v.is_empty()).collect()
+  }
+}
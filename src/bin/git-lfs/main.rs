@@ -0,0 +1,775 @@
+use std::future::Future;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use git2::Oid;
+use git2::Repository;
+use git2_lfs::LfsBuilder;
+use git2_lfs::Pointer;
+use git2_lfs::PointerCheck;
+use git2_lfs::ext::RemoteLfsExt;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::fetch::FetchOptions;
+use git2_lfs::fetch::FetchStatus;
+use git2_lfs::fsck::FsckOptions;
+use git2_lfs::hooks::PrePush;
+use git2_lfs::ls_files::LsFilesSource;
+use git2_lfs::prune::PruneOptions;
+use git2_lfs::push::PushUpdate;
+use git2_lfs::remote::LfsClient;
+use git2_lfs::remote::custom::CustomTransfer;
+use git2_lfs::remote::reqwest::ReqwestLfsClient;
+use git2_lfs::store::FsObjectStore;
+use url::Url;
+
+use args::Args;
+
+mod args;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const ATTRIBUTES: &str = ".gitattributes";
+const LFS_ATTRIBUTES: &str = "filter=lfs diff=lfs merge=lfs -text";
+const TOKEN_ENV: &str = "GIT_LFS_ACCESS_TOKEN";
+const RECENT_REFS_DAYS: u32 = 7;
+
+// Exit code of a command that can't complete or wasn't given right, kept apart from the codes `pointer` and
+// `fsck` report their findings with.
+const FAILED: u8 = 128;
+
+// Exit code of `fsck` when it finds corrupt or missing objects or files that should have been pointers.
+const FSCK_PROBLEMS: u8 = 4;
+
+// Exit codes of `pointer`, which exits with 0 when the pointer is valid and matches the file.
+const POINTER_MISMATCH: u8 = 1;
+const POINTER_INVALID: u8 = 2;
+const POINTER_UNREADABLE: u8 = 3;
+
+const TAKES_VALUE: &[&str] =
+  &["include", "exclude", "I", "X", "id", "path", "file", "pointer", "remote", "r"];
+
+const USAGE: &str = "usage: git lfs <command> [<args>]
+
+  track [<pattern>...]           track patterns with lfs, or list the tracked ones
+  untrack <pattern>...           stop tracking patterns
+  install [--local] [--force]    configure the lfs filter and install the repository's hooks
+  pull [<remote>]                fetch objects for HEAD and check them out
+  fetch [<remote>] [<ref>...]    download objects, see --all, --recent, --include and --exclude
+  push <remote> [<ref>...]       upload objects for refs, or every branch and tag with --all
+  ls-files [--all] [<ref>]       list lfs files, see --long, --size and --name-only
+  status [--json]                show staged, unpushed, missing and unconverted files
+  lock <path>                    lock a file on the remote
+  unlock <path> | --id <id>      release a lock, --force breaks someone else's
+  locks [--path <path>]          list locks on the remote
+  prune [--dry-run]              delete local objects that are no longer needed
+  fsck [<ref>...] [--dry-run]    check local objects and pointers. Exits with 0 when everything is fine
+                                 and 4 when there are problems
+  env                            show the lfs environment
+  pointer [--file <path>]        build the pointer for a file and compare it with --pointer <path> or
+          [--pointer <path>]     --stdin, or just validate the input with --check; --strict only accepts
+          [--stdin] [--check]    canonical pointers. Exits with 0 on a match, 1 on a mismatch, 2 for an
+          [--strict]             invalid pointer and 3 when the input can't be read
+  checkout [<path>...]           replace pointer files in the working tree with their objects
+  version                        show the version
+
+Commands that can't complete and usage errors exit with 128.
+";
+
+fn main() -> ExitCode {
+  let mut args = std::env::args().skip(1);
+  let Some(command) = args.next() else {
+    eprint!("{}", USAGE);
+    return ExitCode::from(FAILED);
+  };
+
+  let result = Args::parse(args, TAKES_VALUE).map_err(Error::from).and_then(|args| run(&command, &args));
+
+  match result {
+    Ok(code) => code,
+    Err(e) => {
+      eprintln!("{}", git2_lfs::report_error(e.as_ref()));
+      ExitCode::from(FAILED)
+    }
+  }
+}
+
+fn run(command: &str, args: &Args) -> Result<ExitCode, Error> {
+  match command {
+    "help" | "--help" | "-h" => {
+      print!("{}", USAGE);
+      return Ok(ExitCode::SUCCESS);
+    }
+    "version" | "--version" => {
+      println!("{}", version());
+      return Ok(ExitCode::SUCCESS);
+    }
+    "install" => return install(args),
+    "env" => return env(),
+    "pointer" => return pointer(args),
+    _ => {}
+  }
+
+  let repo = Repository::open_from_env()?;
+  LfsBuilder::default().install("filter=lfs")?;
+
+  match command {
+    "track" => track(&repo, args),
+    "untrack" => untrack(&repo, args),
+    "pull" => pull(&repo, args),
+    "fetch" => fetch(&repo, args),
+    "push" => push(&repo, args),
+    "ls-files" => ls_files(&repo, args),
+    "status" => status(&repo, args),
+    "lock" => lock(&repo, args),
+    "unlock" => unlock(&repo, args),
+    "locks" => locks(&repo, args),
+    "prune" => prune(&repo, args),
+    "fsck" => fsck(&repo, args),
+    "checkout" => checkout(&repo, args),
+    "clean" => clean(&repo),
+    "smudge" => smudge(&repo),
+    "pre-push" => pre_push(&repo, args),
+    // Written by `install` for parity with git-lfs, which only uses them for lockable files.
+    "post-checkout" | "post-commit" | "post-merge" => Ok(ExitCode::SUCCESS),
+    command => {
+      eprintln!("git-lfs: '{}' is not a command, see 'git lfs help'", command);
+      Ok(ExitCode::from(FAILED))
+    }
+  }
+}
+
+fn version() -> String {
+  format!(
+    "git-lfs/{} (git2-lfs; {} {})",
+    env!("CARGO_PKG_VERSION"),
+    std::env::consts::OS,
+    std::env::consts::ARCH
+  )
+}
+
+fn track(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let path = workdir(repo)?.join(ATTRIBUTES);
+  let mut content = read_attributes(&path)?;
+  let tracked = tracked_patterns(&content);
+
+  if args.positional().is_empty() {
+    println!("Listing tracked patterns");
+    for pattern in tracked {
+      println!("    {} ({})", pattern, ATTRIBUTES);
+    }
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  for pattern in args.positional() {
+    let escaped = pattern.replace(' ', "[[:space:]]");
+    if tracked.contains(&escaped) {
+      println!("\"{}\" already supported", pattern);
+      continue;
+    }
+
+    if !content.is_empty() && !content.ends_with('\n') {
+      content.push('\n');
+    }
+    content.push_str(&format!("{} {}\n", escaped, LFS_ATTRIBUTES));
+    println!("Tracking \"{}\"", pattern);
+  }
+
+  std::fs::write(&path, content)?;
+  Ok(ExitCode::SUCCESS)
+}
+
+fn untrack(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  if args.positional().is_empty() {
+    return Err("usage: git lfs untrack <pattern>...".into());
+  }
+
+  let path = workdir(repo)?.join(ATTRIBUTES);
+  let content = read_attributes(&path)?;
+  let patterns = args.positional().iter().map(|p| p.replace(' ', "[[:space:]]")).collect::<Vec<_>>();
+
+  let mut kept = String::with_capacity(content.len());
+  for line in content.lines() {
+    match lfs_pattern(line) {
+      Some(pattern) if patterns.iter().any(|p| p == pattern) => println!("Untracking \"{}\"", pattern),
+      _ => {
+        kept.push_str(line);
+        kept.push('\n');
+      }
+    }
+  }
+
+  std::fs::write(&path, kept)?;
+  Ok(ExitCode::SUCCESS)
+}
+
+fn read_attributes(path: &Path) -> Result<String, Error> {
+  match std::fs::read_to_string(path) {
+    Ok(content) => Ok(content),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+    Err(e) => Err(e.into()),
+  }
+}
+
+fn tracked_patterns(content: &str) -> Vec<String> {
+  content.lines().filter_map(lfs_pattern).map(str::to_string).collect()
+}
+
+fn lfs_pattern(line: &str) -> Option<&str> {
+  let mut fields = line.split_whitespace();
+  let pattern = fields.next().filter(|p| !p.starts_with('#'))?;
+  fields.any(|f| f == "filter=lfs").then_some(pattern)
+}
+
+fn install(args: &Args) -> Result<ExitCode, Error> {
+  let repo = Repository::open_from_env().ok();
+  let force = args.flag(&["force", "f"]);
+
+  let mut config = match (args.flag(&["local"]), &repo) {
+    (true, Some(repo)) => repo.config()?.open_level(git2::ConfigLevel::Local)?,
+    (true, None) => return Err("--local can only be used inside a repository".into()),
+    (false, _) => git2::Config::open(&global_config_path()?)?,
+  };
+
+  let filter = [("filter.lfs.clean", "git-lfs clean -- %f"), ("filter.lfs.smudge", "git-lfs smudge -- %f")];
+  for (key, value) in filter {
+    if !force
+      && let Ok(existing) = config.get_string(key)
+      && existing != value
+    {
+      return Err(format!("{} is already set to '{}', use --force to overwrite it", key, existing).into());
+    }
+  }
+
+  for (key, value) in filter {
+    config.set_str(key, value)?;
+  }
+  config.set_bool("filter.lfs.required", true)?;
+
+  if let Some(repo) = &repo
+    && !args.flag(&["skip-repo"])
+  {
+    git2_lfs::hooks::install(repo, force)?;
+    println!("Updated git hooks.");
+  }
+
+  println!("Git LFS initialized.");
+  Ok(ExitCode::SUCCESS)
+}
+
+fn global_config_path() -> Result<PathBuf, Error> {
+  match git2::Config::find_global() {
+    Ok(path) => Ok(path),
+    Err(_) => {
+      let home = std::env::var_os("HOME").ok_or("can't find the global git config")?;
+      Ok(Path::new(&home).join(".gitconfig"))
+    }
+  }
+}
+
+fn pull(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let fetched = fetch_objects(repo, &remote_name(repo, args.first()), FetchOptions::default())?;
+
+  let report = repo.lfs_checkout(&[])?;
+  for path in report.missing.iter() {
+    eprintln!("Skipped checking out {}: object is missing", path.display());
+  }
+
+  Ok(if fetched && report.missing.is_empty() { ExitCode::SUCCESS } else { ExitCode::from(FAILED) })
+}
+
+fn fetch(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let refs = args.positional().iter().skip(1).map(String::as_str).collect::<Vec<_>>();
+  let include = [args.values("include"), args.values("I")].concat();
+  let exclude = [args.values("exclude"), args.values("X")].concat();
+
+  let mut options = FetchOptions::default().with_refs(&refs).with_all(args.flag(&["all"]));
+  if args.flag(&["recent"]) {
+    options = options.with_recent_refs_days(RECENT_REFS_DAYS);
+  }
+  if !include.is_empty() {
+    options = options.with_include(&include);
+  }
+  if !exclude.is_empty() {
+    options = options.with_exclude(&exclude);
+  }
+
+  let fetched = fetch_objects(repo, &remote_name(repo, args.first()), options)?;
+  Ok(if fetched { ExitCode::SUCCESS } else { ExitCode::from(FAILED) })
+}
+
+/// Downloads what `options` select, reporting failures on stderr. Returns whether everything arrived.
+fn fetch_objects(repo: &Repository, remote: &str, options: FetchOptions) -> Result<bool, Error> {
+  let plan = repo.lfs_fetch_plan(&options)?;
+  let client = client(repo, remote)?;
+  let results = block_on(client.fetch(&plan))??;
+
+  let mut downloaded = 0;
+  let mut failed = 0;
+  for result in results.iter() {
+    match &result.status {
+      FetchStatus::Downloaded => downloaded += 1,
+      FetchStatus::Present => {}
+      FetchStatus::Failed(e) => {
+        failed += 1;
+        eprintln!("Failed to fetch {} ({}): {}", result.path.display(), result.pointer.hex(), e);
+      }
+    }
+  }
+
+  println!("Fetched {} of {} objects", downloaded, results.len());
+  Ok(failed == 0)
+}
+
+fn push(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let remote = args.first().ok_or("usage: git lfs push <remote> [<ref>...]")?;
+
+  let refs = if args.flag(&["all"]) {
+    let mut refs = Vec::new();
+    for reference in repo.references()? {
+      let name = reference?.name().unwrap_or_default().to_string();
+      if name.starts_with("refs/heads/") || name.starts_with("refs/tags/") {
+        refs.push(name);
+      }
+    }
+    refs
+  } else if args.positional().len() > 1 {
+    args.positional()[1..].to_vec()
+  } else {
+    vec![repo.head()?.name().unwrap_or_default().to_string()]
+  };
+
+  let mut updates = Vec::with_capacity(refs.len());
+  for name in refs.iter() {
+    let reference = repo.resolve_reference_from_short_name(name)?;
+    let name = reference.name().unwrap_or_default();
+    let local = reference.peel_to_commit()?.id();
+    let tracking = name.strip_prefix("refs/heads/").map(|b| format!("refs/remotes/{}/{}", remote, b));
+    let remote_oid = match tracking.and_then(|t| repo.refname_to_id(&t).ok()) {
+      Some(oid) => oid,
+      None => Oid::from_bytes(&[0; 20])?,
+    };
+    updates.push(PushUpdate::new(name, local, name, remote_oid));
+  }
+
+  if args.flag(&["dry-run"]) {
+    for pointer in repo.find_lfs_objects_to_push_for(&updates)? {
+      println!("push {} ({} B)", pointer.hex(), pointer.size());
+    }
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  let client = client(repo, remote)?;
+  block_on(client.push_updates(repo, &updates))??;
+  Ok(ExitCode::SUCCESS)
+}
+
+fn pre_push(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let remote = args.first().ok_or("usage: git lfs pre-push <remote> [<url>]")?;
+  let url = args.positional().get(1).map(String::as_str).unwrap_or(remote);
+
+  let mut input = String::new();
+  std::io::stdin().read_to_string(&mut input)?;
+  let pre_push = PrePush::parse(remote, url, &input)?;
+
+  let client = match repo.find_remote(remote) {
+    Ok(_) => client(repo, remote)?,
+    Err(_) => client(repo, url)?,
+  };
+  block_on(client.pre_push(repo, &pre_push))??;
+  Ok(ExitCode::SUCCESS)
+}
+
+fn ls_files(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let commit = match args.first() {
+    _ if args.flag(&["all", "a"]) => None,
+    Some(rev) => Some(repo.revparse_single(rev)?.peel_to_commit()?),
+    None => Some(repo.head()?.peel_to_commit()?),
+  };
+
+  let long = args.flag(&["long", "l"]);
+  let size = args.flag(&["size", "s"]);
+  let name_only = args.flag(&["name-only", "n"]);
+  let source = commit.as_ref().map_or(LsFilesSource::All, LsFilesSource::Commit);
+
+  for file in repo.lfs_ls_files(source)? {
+    let file = file?;
+    if name_only {
+      println!("{}", file.path.display());
+      continue;
+    }
+
+    let hex = file.pointer.hex();
+    let oid = if long { &hex[..] } else { &hex[..10] };
+    let marker = if file.present { '*' } else { '-' };
+    if size {
+      println!("{} {} {} ({} B)", oid, marker, file.path.display(), file.pointer.size());
+    } else {
+      println!("{} {} {}", oid, marker, file.path.display());
+    }
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
+
+fn status(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let status = repo.lfs_status()?;
+
+  if args.flag(&["json"]) {
+    println!("{}", serde_json::to_string_pretty(&status)?);
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  println!("Objects to be pushed:\n");
+  for pointer in status.unpushed.iter() {
+    println!("\t{} ({} B)", pointer.hex(), pointer.size());
+  }
+
+  println!("\nObjects to be committed:\n");
+  for file in status.staged.iter() {
+    println!("\t{} ({:?})", file.path.display(), file.change);
+  }
+
+  println!("\nObjects missing locally:\n");
+  for file in status.missing.iter() {
+    println!("\t{} ({})", file.path.display(), file.pointer.hex());
+  }
+
+  println!("\nFiles that should be lfs pointers:\n");
+  for path in status.unconverted.iter() {
+    println!("\t{}", path.display());
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
+
+fn lock(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let path = repo_path(repo, args.first().ok_or("usage: git lfs lock <path>")?)?;
+  let client = client(repo, &remote_name(repo, args.value("remote")))?;
+  let lock = block_on(client.lock(&path))??;
+
+  if args.flag(&["json"]) {
+    println!("{}", serde_json::to_string_pretty(&lock)?);
+  } else {
+    println!("Locked {}", lock.path);
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
+
+fn unlock(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let client = client(repo, &remote_name(repo, args.value("remote")))?;
+
+  let id = match (args.value("id"), args.first()) {
+    (Some(id), _) => id.to_string(),
+    (None, Some(path)) => {
+      let path = repo_path(repo, path)?;
+      let locks = block_on(client.locks(Some(&path)))??;
+      locks.into_iter().next().map(|l| l.id).ok_or_else(|| format!("{} isn't locked", path))?
+    }
+    (None, None) => return Err("usage: git lfs unlock <path> | --id <id>".into()),
+  };
+
+  let lock = block_on(client.unlock(&id, args.flag(&["force", "f"])))??;
+  println!("Unlocked {}", lock.path);
+  Ok(ExitCode::SUCCESS)
+}
+
+fn locks(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let client = client(repo, &remote_name(repo, args.value("remote")))?;
+  let path = args.value("path").map(|p| repo_path(repo, p)).transpose()?;
+  let locks = block_on(client.locks(path.as_deref()))??;
+
+  if args.flag(&["json"]) {
+    println!("{}", serde_json::to_string_pretty(&locks)?);
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  for lock in locks.iter() {
+    println!("{}\t{}\tID:{}", lock.path, lock.owner.name, lock.id);
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
+
+fn prune(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let remote = remote_name(repo, args.value("remote"));
+  let options = PruneOptions::default().with_remote(&remote).with_dry_run(args.flag(&["dry-run", "d"]));
+  let plan = repo.lfs_prune_plan(&options)?;
+
+  println!(
+    "{} local objects, {} retained, {} to prune ({} B)",
+    plan.candidates.len() + plan.retained,
+    plan.retained,
+    plan.candidates.len(),
+    plan.bytes()
+  );
+  if plan.dry_run {
+    if args.flag(&["verbose", "v"]) {
+      for pointer in plan.candidates.iter() {
+        println!(" * {}", pointer.hex());
+      }
+    }
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  let report = block_on(client(repo, &remote)?.prune(&plan))??;
+  println!("Deleted {} objects, reclaimed {} B", report.deleted.len(), report.bytes_reclaimed);
+  if !report.unverified.is_empty() {
+    println!("Kept {} objects the remote couldn't confirm it has", report.unverified.len());
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
+
+fn fsck(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let refs = args.positional().iter().map(String::as_str).collect::<Vec<_>>();
+  let options = FsckOptions::default().with_refs(&refs).with_move_bad(!args.flag(&["dry-run", "d"]));
+  let report = repo.lfs_fsck(&options)?;
+
+  for object in report.corrupt.iter() {
+    println!("objects: corruptObject: {} has content {}", object.expected.hex(), object.actual.hex());
+  }
+
+  for object in report.missing.iter() {
+    println!("objects: missing: {} ({}) in {}", object.path.display(), object.pointer.hex(), object.commit);
+  }
+
+  for file in report.unconverted.iter() {
+    println!(
+      "pointer: unexpectedGitObject: {} ({}) should have been a pointer",
+      file.path.display(),
+      file.commit
+    );
+  }
+
+  if report.is_ok() {
+    println!("Git LFS fsck OK");
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  Ok(ExitCode::from(FSCK_PROBLEMS))
+}
+
+fn env() -> Result<ExitCode, Error> {
+  println!("{}", version());
+
+  if let Ok(repo) = Repository::open_from_env() {
+    let remote = remote_name(&repo, None);
+    let endpoint = endpoint(&repo, &remote).map(|url| url.to_string()).unwrap_or_default();
+    println!();
+    println!("Endpoint={} (remote \"{}\")", endpoint, remote);
+    println!("LocalWorkingDir={}", repo.workdir().map(|p| p.display().to_string()).unwrap_or_default());
+    println!("LocalGitDir={}", repo.path().display());
+    println!("LocalMediaDir={}", FsObjectStore::for_repo(&repo)?.root().display());
+  }
+
+  println!("AccessToken={}", if std::env::var_os(TOKEN_ENV).is_some() { "set" } else { "none" });
+
+  let config = git2::Config::open_default()?;
+  for key in ["filter.lfs.clean", "filter.lfs.smudge", "filter.lfs.required"] {
+    println!("git config {} = \"{}\"", key, config.get_string(key).unwrap_or_default());
+  }
+
+  Ok(ExitCode::SUCCESS)
+}
+
+fn pointer(args: &Args) -> Result<ExitCode, Error> {
+  let strict = args.flag(&["strict"]);
+  let stdin = args.flag(&["stdin"]);
+
+  if args.flag(&["check"]) {
+    let input = match (args.value("file"), stdin) {
+      (Some(file), false) => read_input(Some(file)),
+      (None, true) => read_input(None),
+      _ => return Err("usage: git lfs pointer --check (--file <path> | --stdin) [--strict]".into()),
+    };
+
+    return Ok(match input.map(|input| PointerCheck::new(None, &input, strict)) {
+      Ok(PointerCheck::Invalid(e)) => {
+        eprintln!("Invalid pointer: {}", e);
+        ExitCode::from(POINTER_INVALID)
+      }
+      Ok(_) => ExitCode::SUCCESS,
+      Err(code) => code,
+    });
+  }
+
+  let built = match args.value("file") {
+    Some(file) => {
+      let pointer = match read_input(Some(file)) {
+        Ok(content) => Pointer::from_blob_bytes(&content)?,
+        Err(code) => return Ok(code),
+      };
+      eprintln!("Git LFS pointer for {}\n", file);
+      print!("{}", String::from_utf8(pointer.as_bytes()?)?);
+      Some(pointer)
+    }
+    None => None,
+  };
+
+  let input = match (args.value("pointer"), stdin) {
+    (Some(path), _) => read_input(Some(path)),
+    (None, true) => read_input(None),
+    (None, false) if built.is_some() => return Ok(ExitCode::SUCCESS),
+    (None, false) => {
+      return Err("usage: git lfs pointer [--file <path>] [--pointer <path> | --stdin]".into());
+    }
+  };
+
+  let input = match input {
+    Ok(input) => input,
+    Err(code) => return Ok(code),
+  };
+
+  match PointerCheck::new(built.as_ref(), &input, strict) {
+    PointerCheck::Match(pointer) => {
+      if built.is_none() {
+        print!("{}", String::from_utf8(pointer.as_bytes()?)?);
+      }
+      Ok(ExitCode::SUCCESS)
+    }
+    PointerCheck::Mismatch { expected, actual } => {
+      eprintln!("\nPointers do not match: expected {}, got {}", expected, actual);
+      Ok(ExitCode::from(POINTER_MISMATCH))
+    }
+    PointerCheck::Invalid(e) => {
+      eprintln!("\nInvalid pointer: {}", e);
+      Ok(ExitCode::from(POINTER_INVALID))
+    }
+  }
+}
+
+/// Reads a file, or stdin without a path, reporting failures as `pointer`'s exit code for them.
+fn read_input(path: Option<&str>) -> Result<Vec<u8>, ExitCode> {
+  let read = match path {
+    Some(path) => std::fs::read(path),
+    None => {
+      let mut input = Vec::new();
+      std::io::stdin().read_to_end(&mut input).map(|_| input)
+    }
+  };
+
+  read.map_err(|e| {
+    eprintln!("Can't read {}: {}", path.unwrap_or("stdin"), e);
+    ExitCode::from(POINTER_UNREADABLE)
+  })
+}
+
+fn checkout(repo: &Repository, args: &Args) -> Result<ExitCode, Error> {
+  let paths = args.positional().iter().map(String::as_str).collect::<Vec<_>>();
+  let report = repo.lfs_checkout(&paths)?;
+
+  for path in report.missing.iter() {
+    eprintln!("Skipped checking out {}: object is missing", path.display());
+  }
+  println!("Checked out {} files", report.updated.len());
+
+  Ok(ExitCode::SUCCESS)
+}
+
+/// The clean filter: stores stdin as an lfs object and writes its pointer to stdout.
+fn clean(repo: &Repository) -> Result<ExitCode, Error> {
+  let mut input = Vec::new();
+  std::io::stdin().read_to_end(&mut input)?;
+  let mut out = std::io::stdout().lock();
+
+  if Pointer::is_pointer(&input) {
+    out.write_all(&input)?;
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  let pointer = Pointer::from_blob_bytes(&input)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, &input)?;
+  pointer.write_pointer(&mut out)?;
+  Ok(ExitCode::SUCCESS)
+}
+
+/// The smudge filter: replaces the pointer on stdin with its object, downloading it if needed. A pointer
+/// whose object can't be found is written back as is.
+fn smudge(repo: &Repository) -> Result<ExitCode, Error> {
+  let mut input = Vec::new();
+  std::io::stdin().read_to_end(&mut input)?;
+  let mut out = std::io::stdout().lock();
+
+  let Some(pointer) = Pointer::from_str_short(&input) else {
+    out.write_all(&input)?;
+    return Ok(ExitCode::SUCCESS);
+  };
+
+  let store = repo.lfs_object_store()?;
+  if !store.contains(&pointer)?
+    && let Err(e) = client(repo, &remote_name(repo, None)).and_then(|c| Ok(block_on(c.pull(&[pointer]))??))
+  {
+    eprintln!("Error downloading object {}: {}", pointer.hex(), git2_lfs::report_error(e.as_ref()));
+    out.write_all(&input)?;
+    return Ok(ExitCode::SUCCESS);
+  }
+
+  std::io::copy(&mut store.open(&pointer)?, &mut out)?;
+  Ok(ExitCode::SUCCESS)
+}
+
+fn client<'r>(repo: &'r Repository, remote: &str) -> Result<LfsClient<'r, ReqwestLfsClient>, Error> {
+  let url = endpoint(repo, remote).ok_or_else(|| format!("no lfs endpoint for remote '{}'", remote))?;
+  let token = std::env::var(TOKEN_ENV).ok();
+
+  let mut client = LfsClient::new(repo, ReqwestLfsClient::new(url, token))?;
+  for transfer in CustomTransfer::all_from_config(&repo.config()?)? {
+    client = client.custom_transfer(transfer.with_remote(remote));
+  }
+
+  Ok(client)
+}
+
+/// `lfs.url`, then `remote.<name>.lfsurl`, then the url git-lfs derives from the remote's url. `remote` may
+/// also be a url itself.
+fn endpoint(repo: &Repository, remote: &str) -> Option<Url> {
+  let config = repo.config().ok()?;
+  for key in ["lfs.url".to_string(), format!("remote.{}.lfsurl", remote)] {
+    if let Ok(url) = config.get_string(&key) {
+      return Url::parse(&url).ok();
+    }
+  }
+
+  match repo.find_remote(remote) {
+    Ok(remote) => remote.lfs_url(),
+    Err(_) => repo.remote_anonymous(remote).ok()?.lfs_url(),
+  }
+}
+
+/// The given remote, or the current branch's upstream remote, or `origin`.
+fn remote_name(repo: &Repository, remote: Option<&str>) -> String {
+  if let Some(remote) = remote {
+    return remote.to_string();
+  }
+
+  let upstream = repo.head().ok().and_then(|head| {
+    let branch = head.shorthand().unwrap_or_default().to_string();
+    repo.config().ok()?.get_string(&format!("branch.{}.remote", branch)).ok()
+  });
+
+  upstream.unwrap_or_else(|| "origin".to_string())
+}
+
+fn workdir(repo: &Repository) -> Result<&Path, Error> {
+  repo.workdir().ok_or_else(|| "this command must be run in a work tree".into())
+}
+
+/// A path given on the command line, relative to the top of the working tree with `/` separators.
+fn repo_path(repo: &Repository, path: &str) -> Result<String, Error> {
+  let absolute = std::env::current_dir()?.join(path);
+  let absolute = absolute.canonicalize().unwrap_or(absolute);
+  let workdir = workdir(repo)?.canonicalize()?;
+
+  let relative =
+    absolute.strip_prefix(&workdir).map_err(|_| format!("{} is outside the repository", path))?;
+  let components = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>();
+  Ok(components.join("/"))
+}
+
+fn block_on<F: Future>(future: F) -> Result<F::Output, Error> {
+  Ok(tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(future))
+}
@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+
+use git2::IndexEntry;
+use git2::IndexTime;
+use git2::Pathspec;
+use git2::PathspecFlags;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::pointer::POINTER_ROUGH_LEN;
+use crate::scan::INDEX_ENTRY_STAGE_MASK;
+use crate::scan::PointerScanner;
+use crate::store::ObjectStore;
+
+#[derive(Debug, Default, Clone)]
+pub struct CheckoutReport {
+  /// Files that held pointer text and now hold the object content.
+  pub updated: Vec<PathBuf>,
+  /// Files that are still pointers because their object isn't in the local store.
+  pub missing: Vec<PathBuf>,
+}
+
+pub(crate) fn checkout(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  pathspec: &[&str],
+) -> Result<CheckoutReport, Error> {
+  let mut report = CheckoutReport::default();
+
+  let Some(workdir) = repo.workdir() else {
+    return Ok(report);
+  };
+
+  let pathspec = if pathspec.is_empty() { None } else { Some(Pathspec::new(pathspec.iter().copied())?) };
+
+  let mut index = repo.index()?;
+  index.read(false)?;
+
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut updated_entries = Vec::new();
+
+  for entry in index.iter() {
+    if entry.flags & INDEX_ENTRY_STAGE_MASK != 0 {
+      continue;
+    }
+
+    let path = PathBuf::from(std::str::from_utf8(&entry.path)?);
+    if let Some(pathspec) = pathspec.as_ref()
+      && !pathspec.matches_path(&path, PathspecFlags::DEFAULT)
+    {
+      continue;
+    }
+
+    let Ok(Some(pointer)) = scanner.pointer(entry.id) else {
+      continue;
+    };
+
+    if dangling_pointer(&workdir.join(&path))? != Some(pointer) {
+      continue;
+    }
+
+    if !store.contains(&pointer)? {
+      warn!(pointer = %pointer, path = %path.display(), "checkout: lfs object is missing, keeping the pointer");
+      report.missing.push(path);
+      continue;
+    }
+
+    let metadata = materialize(store, &pointer, &workdir.join(&path))?;
+    debug!(pointer = %pointer, path = %path.display(), "checkout: replaced pointer with object content");
+
+    updated_entries.push(with_stat(entry, &metadata));
+    report.updated.push(path);
+  }
+
+  if !updated_entries.is_empty() {
+    for entry in updated_entries.iter() {
+      index.add(entry)?;
+    }
+
+    index.write()?;
+  }
+
+  Ok(report)
+}
+
+fn dangling_pointer(path: &Path) -> Result<Option<Pointer>, Error> {
+  let metadata = match path.symlink_metadata() {
+    Ok(metadata) => metadata,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(e) => return Err(e.into()),
+  };
+
+  if !metadata.is_file() || !POINTER_ROUGH_LEN.contains(&(metadata.len() as usize)) {
+    return Ok(None);
+  }
+
+  Ok(Pointer::from_str_short(&std::fs::read(path)?))
+}
+
+/// Replaces the file at `path` with the object content through a temp file next to it, keeping its
+/// permissions, and returns the metadata of the result.
+fn materialize(store: &dyn ObjectStore, pointer: &Pointer, path: &Path) -> Result<std::fs::Metadata, Error> {
+  let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+  let tmp_path = path.with_file_name(format!(".{}.{}.lfs-checkout.tmp", file_name, std::process::id()));
+
+  let result = (|| {
+    let mut tmp = File::create(&tmp_path)?;
+    std::io::copy(&mut store.open(pointer)?, &mut tmp)?;
+    tmp.sync_all()?;
+    tmp.set_permissions(path.metadata()?.permissions())?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok::<_, Error>(())
+  })();
+
+  if let Err(e) = result {
+    let _ = std::fs::remove_file(&tmp_path);
+    return Err(e);
+  }
+
+  Ok(path.metadata()?)
+}
+
+fn with_stat(entry: IndexEntry, metadata: &std::fs::Metadata) -> IndexEntry {
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::MetadataExt;
+
+    IndexEntry {
+      ctime: IndexTime::new(metadata.ctime() as i32, metadata.ctime_nsec() as u32),
+      mtime: IndexTime::new(metadata.mtime() as i32, metadata.mtime_nsec() as u32),
+      dev: metadata.dev() as u32,
+      ino: metadata.ino() as u32,
+      uid: metadata.uid(),
+      gid: metadata.gid(),
+      file_size: metadata.size() as u32,
+      ..entry
+    }
+  }
+
+  #[cfg(not(unix))]
+  {
+    let mtime =
+      metadata.modified().ok().and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok()).unwrap_or_default();
+
+    IndexEntry {
+      mtime: IndexTime::new(mtime.as_secs() as i32, mtime.subsec_nanos()),
+      file_size: metadata.len() as u32,
+      ..entry
+    }
+  }
+}
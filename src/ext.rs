@@ -1,7 +1,8 @@
 use std::borrow::Cow;
 use std::collections::HashSet;
+use std::ops::Deref;
 use std::path::Path;
-use std::str::FromStr;
+use std::sync::Arc;
 
 use git2::*;
 use tracing::*;
@@ -9,9 +10,26 @@ use url::Url;
 
 use crate::Error;
 use crate::Pointer;
-use crate::pointer::POINTER_ROUGH_LEN;
+use crate::checkout::CheckoutReport;
+use crate::fetch::FetchOptions;
+use crate::fetch::FetchPlan;
+use crate::fsck::FsckOptions;
+use crate::fsck::FsckReport;
+use crate::ls_files::LfsFiles;
+use crate::ls_files::LsFilesSource;
+use crate::migrate::MigrateInfo;
+use crate::migrate::MigrateOptions;
+use crate::migrate::MigrateReport;
+use crate::prune::PruneOptions;
+use crate::prune::PrunePlan;
+use crate::push::PushUpdate;
+use crate::scan::PointerScanner;
+use crate::status::LfsStatus;
+use crate::store::FsObjectStore;
+use crate::store::ObjectStore;
 
 pub trait RepoLfsExt {
+  fn lfs_object_store(&self) -> Result<Arc<dyn ObjectStore>, Error>;
   fn get_lfs_blob_content<'r>(&self, blob: &'r git2::Blob<'_>) -> Result<Cow<'r, [u8]>, Error>;
   fn find_tree_missing_lfs_objects(&self, tree: &git2::Tree<'_>) -> Result<Vec<Pointer>, Error>;
   fn try_get_dangling_pointer(&self, rel_path: &Path) -> Result<Option<Pointer>, Error>;
@@ -20,6 +38,24 @@ pub trait RepoLfsExt {
     local_branch: &git2::Reference,
     upstream_branch: Option<&git2::Reference>,
   ) -> Result<Vec<Pointer>, Error>;
+  /// Objects to upload for a push of several refs at once, hiding everything on remote-tracking refs.
+  fn find_lfs_objects_to_push_for(&self, updates: &[PushUpdate]) -> Result<Vec<Pointer>, Error>;
+  fn lfs_fsck(&self, options: &FsckOptions) -> Result<FsckReport, Error>;
+  fn lfs_prune_plan(&self, options: &PruneOptions) -> Result<PrunePlan, Error>;
+  fn lfs_fetch_plan(&self, options: &FetchOptions) -> Result<FetchPlan, Error>;
+  /// Replaces pointer files in the working tree with their objects, for index entries matching `pathspec`
+  /// (all of them if it's empty), and refreshes their stat data in the index.
+  fn lfs_checkout(&self, pathspec: &[&str]) -> Result<CheckoutReport, Error>;
+  /// Lists the pointers `source` holds, lazily, with whether each object is available locally.
+  fn lfs_ls_files<'a>(&'a self, source: LsFilesSource<'a>) -> Result<LfsFiles<'a>, Error>;
+  /// Rewrites the history of the selected refs, replacing matching blobs with lfs pointers.
+  fn lfs_migrate_import(&self, options: &MigrateOptions) -> Result<MigrateReport, Error>;
+  /// Rewrites the history of the selected refs, replacing pointers with their objects. Every object has to
+  /// be available locally; [`crate::remote::LfsClient::migrate_export`] downloads missing ones first.
+  fn lfs_migrate_export(&self, options: &MigrateOptions) -> Result<MigrateReport, Error>;
+  /// Groups blob sizes in the history of the selected refs without changing anything.
+  fn lfs_migrate_info(&self, options: &MigrateOptions) -> Result<MigrateInfo, Error>;
+  fn lfs_status(&self) -> Result<LfsStatus, Error>;
 }
 
 pub trait RemoteLfsExt {
@@ -29,15 +65,108 @@ pub trait RemoteLfsExt {
 impl RemoteLfsExt for Remote<'_> {
   fn lfs_url(&self) -> Option<Url> {
     let url = self.url()?;
-    let url = url.trim_end_matches("/");
-    let url =
-      if url.ends_with(".git") { format!("{}/info/lfs", url) } else { format!("{}.git/info/lfs", url) };
+    lfs_url(url)
+  }
+}
+
+pub(crate) fn lfs_url(url: &str) -> Option<Url> {
+  let url = url.trim_end_matches("/");
+  let url = if url.ends_with(".git") { format!("{}/info/lfs", url) } else { format!("{}.git/info/lfs", url) };
+
+  Url::parse(&url).ok()
+}
+
+/// A repository paired with an explicit object store, for when lfs objects don't live in `.git/lfs/objects`.
+pub struct LfsRepository<'r> {
+  repo: &'r git2::Repository,
+  store: Arc<dyn ObjectStore>,
+}
+
+impl<'r> LfsRepository<'r> {
+  pub fn new(repo: &'r git2::Repository, store: Arc<dyn ObjectStore>) -> Self {
+    Self { repo, store }
+  }
+}
+
+impl Deref for LfsRepository<'_> {
+  type Target = git2::Repository;
 
-    Url::parse(&url).ok()
+  fn deref(&self) -> &Self::Target {
+    self.repo
   }
 }
 
 impl RepoLfsExt for git2::Repository {
+  fn lfs_object_store(&self) -> Result<Arc<dyn ObjectStore>, Error> {
+    Ok(Arc::new(FsObjectStore::for_repo(self)?))
+  }
+
+  fn get_lfs_blob_content<'r>(&self, blob: &'r git2::Blob<'_>) -> Result<Cow<'r, [u8]>, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).get_lfs_blob_content(blob)
+  }
+
+  fn find_tree_missing_lfs_objects(&self, tree: &git2::Tree<'_>) -> Result<Vec<Pointer>, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).find_tree_missing_lfs_objects(tree)
+  }
+
+  fn try_get_dangling_pointer(&self, rel_path: &Path) -> Result<Option<Pointer>, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).try_get_dangling_pointer(rel_path)
+  }
+
+  fn find_lfs_objects_to_push(
+    &self,
+    local_branch: &git2::Reference,
+    upstream_branch: Option<&git2::Reference>,
+  ) -> Result<Vec<Pointer>, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).find_lfs_objects_to_push(local_branch, upstream_branch)
+  }
+
+  fn find_lfs_objects_to_push_for(&self, updates: &[PushUpdate]) -> Result<Vec<Pointer>, Error> {
+    crate::push::objects_to_push(self, updates)
+  }
+
+  fn lfs_fsck(&self, options: &FsckOptions) -> Result<FsckReport, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).lfs_fsck(options)
+  }
+
+  fn lfs_prune_plan(&self, options: &PruneOptions) -> Result<PrunePlan, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).lfs_prune_plan(options)
+  }
+
+  fn lfs_fetch_plan(&self, options: &FetchOptions) -> Result<FetchPlan, Error> {
+    crate::fetch::plan(self, options)
+  }
+
+  fn lfs_checkout(&self, pathspec: &[&str]) -> Result<CheckoutReport, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).lfs_checkout(pathspec)
+  }
+
+  fn lfs_ls_files<'a>(&'a self, source: LsFilesSource<'a>) -> Result<LfsFiles<'a>, Error> {
+    crate::ls_files::ls_files(self, self.lfs_object_store()?, source)
+  }
+
+  fn lfs_migrate_import(&self, options: &MigrateOptions) -> Result<MigrateReport, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).lfs_migrate_import(options)
+  }
+
+  fn lfs_migrate_export(&self, options: &MigrateOptions) -> Result<MigrateReport, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).lfs_migrate_export(options)
+  }
+
+  fn lfs_migrate_info(&self, options: &MigrateOptions) -> Result<MigrateInfo, Error> {
+    crate::migrate::info(self, options)
+  }
+
+  fn lfs_status(&self) -> Result<LfsStatus, Error> {
+    LfsRepository::new(self, self.lfs_object_store()?).lfs_status()
+  }
+}
+
+impl RepoLfsExt for LfsRepository<'_> {
+  fn lfs_object_store(&self) -> Result<Arc<dyn ObjectStore>, Error> {
+    Ok(Arc::clone(&self.store))
+  }
+
   fn try_get_dangling_pointer(&self, rel_path: &Path) -> Result<Option<Pointer>, Error> {
     let Some(workdir) = self.workdir() else {
       return Ok(None);
@@ -59,40 +188,49 @@ impl RepoLfsExt for git2::Repository {
       return Ok(Cow::Borrowed(blob.content()));
     };
 
-    let path = self.path().join("lfs/objects").join(pointer.path());
-
-    if !path.exists() {
+    if !self.store.contains(&pointer)? {
       warn!(pointer = %pointer, "lfs object not found; returning the original content");
       return Ok(Cow::Borrowed(blob.content()));
     }
 
-    let content = std::fs::read(path)?;
+    let content = self.store.read_to_vec(&pointer)?;
     Ok(Cow::Owned(content))
   }
 
   fn find_tree_missing_lfs_objects(&self, tree: &git2::Tree<'_>) -> Result<Vec<Pointer>, Error> {
     let mut missing = HashSet::<Pointer>::new();
+    let mut scanner = PointerScanner::new(self.repo)?;
+    let mut store_error = None;
 
-    tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+    let walked = tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
       let Some(ObjectType::Blob) = entry.kind() else {
         return TreeWalkResult::Ok;
       };
 
       let oid = entry.id();
-      let Ok(blob) = self.find_blob(oid) else {
-        warn!(
-          "blob '{}' ({}{}) not found during traversing tree {}",
-          oid,
-          dir,
-          entry.name().unwrap_or_default(),
-          tree.id()
-        );
+      let pointer = match scanner.pointer(oid) {
+        Ok(pointer) => pointer,
+        Err(e) => {
+          warn!(
+            "blob '{}' ({}{}) can't be read during traversing tree {}: {}",
+            oid,
+            dir,
+            entry.name().unwrap_or_default(),
+            tree.id(),
+            e
+          );
+
+          return TreeWalkResult::Ok;
+        }
+      };
 
+      let Some(pointer) = pointer else {
         return TreeWalkResult::Ok;
       };
 
-      match Pointer::from_str_short(blob.content()) {
-        Some(pointer) if !self.path().join("lfs/objects").join(pointer.path()).exists() => {
+      match self.store.contains(&pointer) {
+        Ok(true) => (),
+        Ok(false) => {
           debug!(
             "blob '{}' ({}{}) is lfs pointer but object is missing",
             oid,
@@ -102,11 +240,19 @@ impl RepoLfsExt for git2::Repository {
 
           missing.insert(pointer);
         }
-        _ => (),
+        Err(e) => {
+          store_error = Some(e);
+          return TreeWalkResult::Abort;
+        }
       }
 
       TreeWalkResult::Ok
-    })?;
+    });
+
+    if let Some(e) = store_error {
+      return Err(e);
+    }
+    walked?;
 
     Ok(missing.into_iter().collect())
   }
@@ -116,46 +262,70 @@ impl RepoLfsExt for git2::Repository {
     local_branch: &git2::Reference,
     upstream_branch: Option<&git2::Reference>,
   ) -> Result<Vec<Pointer>, Error> {
-    let mut objects_to_push = HashSet::new();
+    let (upstream_name, upstream) = match upstream_branch {
+      Some(upstream) => (upstream.name().unwrap_or_default(), upstream.peel_to_commit()?.id()),
+      None => ("", Oid::from_bytes(&[0; 20])?),
+    };
 
-    let mut revwalk = self.revwalk()?;
+    let update = PushUpdate::new(
+      local_branch.name().unwrap_or_default(),
+      local_branch.peel_to_commit()?.id(),
+      upstream_name,
+      upstream,
+    );
 
-    revwalk.push(local_branch.peel_to_commit()?.id())?;
+    self.find_lfs_objects_to_push_for(&[update])
+  }
 
-    if let Some(upstream_branch) = upstream_branch {
-      revwalk.hide(upstream_branch.peel_to_commit()?.id())?;
-    }
+  fn find_lfs_objects_to_push_for(&self, updates: &[PushUpdate]) -> Result<Vec<Pointer>, Error> {
+    crate::push::objects_to_push(self.repo, updates)
+  }
 
-    for commit in revwalk {
-      let commit = self.find_commit(commit?)?;
-      let tree = commit.tree()?;
+  fn lfs_fsck(&self, options: &FsckOptions) -> Result<FsckReport, Error> {
+    crate::fsck::fsck(self.repo, self.store.as_ref(), options)
+  }
 
-      tree.walk(git2::TreeWalkMode::PostOrder, |_, entry| {
-        let Some(ObjectType::Blob) = entry.kind() else {
-          return TreeWalkResult::Ok;
-        };
+  fn lfs_prune_plan(&self, options: &PruneOptions) -> Result<PrunePlan, Error> {
+    crate::prune::plan(self.repo, self.store.as_ref(), options)
+  }
 
-        let oid = entry.id();
+  fn lfs_fetch_plan(&self, options: &FetchOptions) -> Result<FetchPlan, Error> {
+    crate::fetch::plan(self.repo, options)
+  }
 
-        let Ok(blob) = self.find_blob(oid) else {
-          return TreeWalkResult::Ok;
-        };
+  fn lfs_checkout(&self, pathspec: &[&str]) -> Result<CheckoutReport, Error> {
+    crate::checkout::checkout(self.repo, self.store.as_ref(), pathspec)
+  }
 
-        if !POINTER_ROUGH_LEN.contains(&blob.size()) {
-          return TreeWalkResult::Ok;
-        }
+  fn lfs_ls_files<'a>(&'a self, source: LsFilesSource<'a>) -> Result<LfsFiles<'a>, Error> {
+    crate::ls_files::ls_files(self.repo, Arc::clone(&self.store), source)
+  }
 
-        let Ok(pointer) = Pointer::from_str(String::from_utf8_lossy(blob.content()).as_ref()) else {
-          debug!(oid = %oid, "skipping non-lfs pointer file");
-          return TreeWalkResult::Ok;
-        };
+  fn lfs_migrate_import(&self, options: &MigrateOptions) -> Result<MigrateReport, Error> {
+    crate::migrate::import(self.repo, self.store.as_ref(), options)
+  }
 
-        debug!(blob = %oid, commit = %commit.id(), "found lfs-pointer!");
-        objects_to_push.insert(pointer);
-        TreeWalkResult::Ok
-      })?;
-    }
+  fn lfs_migrate_export(&self, options: &MigrateOptions) -> Result<MigrateReport, Error> {
+    crate::migrate::export(self.repo, self.store.as_ref(), options)
+  }
+
+  fn lfs_migrate_info(&self, options: &MigrateOptions) -> Result<MigrateInfo, Error> {
+    crate::migrate::info(self.repo, options)
+  }
+
+  fn lfs_status(&self) -> Result<LfsStatus, Error> {
+    let unpushed = match self.head() {
+      Ok(head) if head.is_branch() => {
+        let upstream = self
+          .branch_upstream_name(head.name().unwrap_or_default())
+          .ok()
+          .and_then(|name| self.find_reference(name.as_str().unwrap_or_default()).ok());
+
+        self.find_lfs_objects_to_push(&head, upstream.as_ref())?
+      }
+      _ => Vec::new(),
+    };
 
-    Ok(objects_to_push.into_iter().collect())
+    crate::status::status(self.repo, self.store.as_ref(), unpushed)
   }
 }
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use git2::Oid;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::remote::RemoteError;
+use crate::scan::PathFilter;
+use crate::scan::PointerScanner;
+use crate::scan::recent_commits;
+use crate::scan::recent_ref_tips;
+
+#[derive(Debug, Default, Clone)]
+pub struct FetchOptions {
+  refs: Vec<String>,
+  all: bool,
+  recent_refs_days: Option<u32>,
+  recent_commits_days: u32,
+  include: Vec<String>,
+  exclude: Vec<String>,
+}
+
+/// Objects referenced by the selected commits, each with the first path it was found under.
+#[derive(Debug, Clone)]
+pub struct FetchPlan {
+  pub objects: Vec<FetchObject>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchObject {
+  pub pointer: Pointer,
+  pub path: PathBuf,
+  /// The ref the object was found under, when it was reached from one named in the options.
+  pub ref_name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct FetchResult {
+  pub pointer: Pointer,
+  pub path: PathBuf,
+  pub status: FetchStatus,
+}
+
+#[derive(Debug)]
+pub enum FetchStatus {
+  Present,
+  Downloaded,
+  Failed(RemoteError),
+}
+
+impl FetchOptions {
+  /// Refs to fetch for; `HEAD` when none are given.
+  pub fn with_refs(mut self, refs: &[&str]) -> Self {
+    self.refs = refs.iter().map(|r| r.to_string()).collect();
+    self
+  }
+
+  /// Fetch every object reachable from any ref, ignoring the other ref options.
+  pub fn with_all(mut self, all: bool) -> Self {
+    self.all = all;
+    self
+  }
+
+  /// Also fetch for branches and remote-tracking refs whose tip was committed within `days`.
+  pub fn with_recent_refs_days(mut self, days: u32) -> Self {
+    self.recent_refs_days = Some(days);
+    self
+  }
+
+  /// Fetch for commits made within `days` of each ref's tip rather than the tip alone.
+  pub fn with_recent_commits_days(mut self, days: u32) -> Self {
+    self.recent_commits_days = days;
+    self
+  }
+
+  /// Only fetch objects under these pathspecs. Defaults to `lfs.fetchinclude`.
+  pub fn with_include(mut self, include: &[&str]) -> Self {
+    self.include = include.iter().map(|p| p.to_string()).collect();
+    self
+  }
+
+  /// Skip objects under these pathspecs. Defaults to `lfs.fetchexclude`.
+  pub fn with_exclude(mut self, exclude: &[&str]) -> Self {
+    self.exclude = exclude.iter().map(|p| p.to_string()).collect();
+    self
+  }
+}
+
+impl FetchResult {
+  pub fn is_ok(&self) -> bool {
+    !matches!(self.status, FetchStatus::Failed(_))
+  }
+}
+
+/// `lfs.fetchinclude`-style comma separated patterns, unless some were given explicitly.
+fn configured_patterns(config: &git2::Config, explicit: &[String], key: &str) -> Vec<String> {
+  if !explicit.is_empty() {
+    return explicit.to_vec();
+  }
+
+  let configured = config.get_string(key).unwrap_or_default();
+  configured.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect()
+}
+
+pub(crate) fn plan(repo: &git2::Repository, options: &FetchOptions) -> Result<FetchPlan, Error> {
+  let config = repo.config()?;
+  let filter = PathFilter::new(
+    &configured_patterns(&config, &options.include, "lfs.fetchinclude"),
+    &configured_patterns(&config, &options.exclude, "lfs.fetchexclude"),
+  )?;
+  let commits = select_commits(repo, options)?;
+
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut objects = HashMap::<Pointer, (PathBuf, Option<String>)>::new();
+
+  for (commit, ref_name) in commits {
+    let tree = repo.find_commit(commit)?.tree()?;
+    scanner.walk_tree(&tree, |path, _, pointer| {
+      let path = Path::new(path);
+      if let Some(pointer) = pointer
+        && !objects.contains_key(&pointer)
+        && filter.matches(path)
+      {
+        objects.insert(pointer, (path.to_path_buf(), ref_name.clone()));
+      }
+    })?;
+  }
+
+  let mut objects = objects
+    .into_iter()
+    .map(|(pointer, (path, ref_name))| FetchObject { pointer, path, ref_name })
+    .collect::<Vec<_>>();
+  objects.sort_by(|a, b| a.path.cmp(&b.path));
+
+  debug!(objects = objects.len(), "fetch: planned");
+  Ok(FetchPlan { objects })
+}
+
+/// The commits to fetch for, each with the full name of the ref it was reached from, if any.
+fn select_commits(
+  repo: &git2::Repository,
+  options: &FetchOptions,
+) -> Result<Vec<(Oid, Option<String>)>, Error> {
+  if options.all {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_glob("refs/*")?;
+    return Ok(revwalk.map(|c| c.map(|c| (c, None))).collect::<Result<Vec<_>, _>>()?);
+  }
+
+  let refs = if options.refs.is_empty() { vec!["HEAD".to_string()] } else { options.refs.clone() };
+
+  let mut tips = Vec::new();
+  for r in refs.iter() {
+    let (object, reference) = repo.revparse_ext(r)?;
+    let ref_name = reference
+      .and_then(|r| r.resolve().ok())
+      .and_then(|r| std::str::from_utf8(r.name_bytes()).ok().map(str::to_string))
+      .filter(|name| name.starts_with("refs/"));
+    tips.push((object.peel_to_commit()?.id(), ref_name));
+  }
+
+  if let Some(days) = options.recent_refs_days {
+    tips.extend(recent_ref_tips(repo, days)?.into_iter().map(|tip| (tip, None)));
+  }
+
+  let mut seen = HashSet::new();
+  let mut commits = Vec::new();
+  for (tip, ref_name) in tips {
+    let recent = recent_commits(repo, tip, options.recent_commits_days)?;
+    commits.extend(recent.into_iter().filter(|c| seen.insert(*c)).map(|c| (c, ref_name.clone())));
+  }
+
+  Ok(commits)
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use git2::AttrCheckFlags;
+use git2::Index;
+use git2::Oid;
+use sha2::Digest;
+use sha2::Sha256;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::scan::PointerScanner;
+use crate::store::ObjectStore;
+
+#[derive(Debug, Default, Clone)]
+pub struct FsckOptions {
+  refs: Vec<String>,
+  history: bool,
+  move_bad: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct FsckReport {
+  pub corrupt: Vec<CorruptObject>,
+  pub missing: Vec<MissingObject>,
+  pub unconverted: Vec<UnconvertedFile>,
+}
+
+/// A local object whose content doesn't match the pointer it is stored under.
+#[derive(Debug, Clone)]
+pub struct CorruptObject {
+  pub expected: Pointer,
+  pub actual: Pointer,
+  pub moved: bool,
+}
+
+/// A pointer committed in `commit` whose object isn't in the local store.
+#[derive(Debug, Clone)]
+pub struct MissingObject {
+  pub pointer: Pointer,
+  pub path: PathBuf,
+  pub commit: Oid,
+}
+
+/// A blob that matches a `filter=lfs` pattern but was committed as is.
+#[derive(Debug, Clone)]
+pub struct UnconvertedFile {
+  pub path: PathBuf,
+  pub blob: Oid,
+  pub size: usize,
+  pub commit: Oid,
+}
+
+impl FsckOptions {
+  pub fn with_refs(mut self, refs: &[&str]) -> Self {
+    self.refs = refs.iter().map(|r| r.to_string()).collect();
+    self
+  }
+
+  pub fn with_history(mut self, history: bool) -> Self {
+    self.history = history;
+    self
+  }
+
+  pub fn with_move_bad(mut self, move_bad: bool) -> Self {
+    self.move_bad = move_bad;
+    self
+  }
+}
+
+impl FsckReport {
+  pub fn is_ok(&self) -> bool {
+    self.corrupt.is_empty() && self.missing.is_empty() && self.unconverted.is_empty()
+  }
+}
+
+pub(crate) fn fsck(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  options: &FsckOptions,
+) -> Result<FsckReport, Error> {
+  let mut report = FsckReport::default();
+  let mut expected_sizes = HashMap::new();
+
+  check_pointers(repo, store, options, &mut report, &mut expected_sizes)?;
+
+  for object in store.iter()? {
+    let object = object?;
+
+    let actual = rehash(store, &object)?;
+    let expected =
+      Pointer::from_parts(object.hash(), expected_sizes.get(object.hash()).copied().unwrap_or(object.size()));
+
+    if actual == expected {
+      continue;
+    }
+
+    error!(expected = %expected, actual = %actual, "fsck: corrupt lfs object");
+
+    if options.move_bad {
+      store.quarantine(&object)?;
+    }
+
+    report.corrupt.push(CorruptObject { expected, actual, moved: options.move_bad });
+  }
+
+  Ok(report)
+}
+
+fn rehash(store: &dyn ObjectStore, object: &Pointer) -> Result<Pointer, Error> {
+  let mut reader = store.open(object)?;
+  let mut hasher = Sha256::new();
+  let size = std::io::copy(&mut reader, &mut hasher)?;
+
+  Ok(Pointer::from_parts(hasher.finalize().as_slice(), size as usize))
+}
+
+fn check_pointers(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  options: &FsckOptions,
+  report: &mut FsckReport,
+  expected_sizes: &mut HashMap<[u8; 32], usize>,
+) -> Result<(), Error> {
+  let refs = if options.refs.is_empty() { vec!["HEAD".to_string()] } else { options.refs.clone() };
+
+  let mut commits = Vec::new();
+  for r in refs.iter() {
+    match repo.revparse_single(r).and_then(|object| object.peel_to_commit()) {
+      Ok(commit) => commits.push(commit.id()),
+      Err(e)
+        if r == "HEAD" && matches!(e.code(), git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound) =>
+      {
+        debug!("fsck: HEAD is unborn, skipping pointer checks")
+      }
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  if options.history && !commits.is_empty() {
+    let mut revwalk = repo.revwalk()?;
+    for commit in commits.iter() {
+      revwalk.push(*commit)?;
+    }
+
+    commits = revwalk.collect::<Result<Vec<_>, _>>()?;
+  }
+
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut missing_seen = HashSet::new();
+  let mut unconverted_seen = HashSet::new();
+  let attributes = CommitAttributes::new(repo)?;
+
+  for commit_id in commits {
+    let tree = repo.find_commit(commit_id)?.tree()?;
+    let mut blobs = Vec::new();
+
+    scanner.walk_new_trees(&tree, |path, blob, pointer| blobs.push((path.to_string(), blob, pointer)))?;
+    let mut attributes_loaded = false;
+
+    for (path, blob, pointer) in blobs {
+      match pointer {
+        Some(pointer) => {
+          expected_sizes.insert(*pointer.hash(), pointer.size());
+
+          if !store.contains(&pointer)? && missing_seen.insert((pointer, path.clone())) {
+            warn!(pointer = %pointer, path = %path, commit = %commit_id, "fsck: lfs object is missing");
+            report.missing.push(MissingObject { pointer, path: PathBuf::from(path), commit: commit_id });
+          }
+        }
+        None => {
+          if unconverted_seen.contains(&blob) {
+            continue;
+          }
+
+          if !attributes_loaded {
+            attributes.load(&tree)?;
+            attributes_loaded = true;
+          }
+
+          let is_lfs = attributes.is_lfs(Path::new(&path))?;
+          let size = scanner.header(blob)?.0;
+
+          if is_lfs && size > 0 && unconverted_seen.insert(blob) {
+            warn!(path = %path, blob = %blob, commit = %commit_id, "fsck: file should be an lfs pointer");
+            report.unconverted.push(UnconvertedFile {
+              path: PathBuf::from(path),
+              blob,
+              size,
+              commit: commit_id,
+            });
+          }
+        }
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Answers attribute lookups from the `.gitattributes` files committed in a tree rather than the working
+/// tree's, through a second handle on the repository whose index is swapped for the tree.
+struct CommitAttributes {
+  repo: git2::Repository,
+}
+
+impl CommitAttributes {
+  fn new(repo: &git2::Repository) -> Result<Self, Error> {
+    Ok(Self { repo: git2::Repository::open(repo.path())? })
+  }
+
+  fn load(&self, tree: &git2::Tree<'_>) -> Result<(), Error> {
+    let mut index = Index::new()?;
+    index.read_tree(tree)?;
+    self.repo.set_index(&mut index)?;
+    Ok(())
+  }
+
+  fn is_lfs(&self, path: &Path) -> Result<bool, Error> {
+    Ok(self.repo.get_attr(path, "filter", AttrCheckFlags::INDEX_ONLY)? == Some("lfs"))
+  }
+}
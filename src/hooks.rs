@@ -0,0 +1,118 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use git2::Oid;
+use tracing::*;
+use url::Url;
+
+use crate::Error;
+use crate::push::PushUpdate;
+
+/// Hooks [`install`] writes, each handing its arguments over to `git lfs <hook>`.
+pub const HOOKS: [&str; 4] = ["pre-push", "post-checkout", "post-commit", "post-merge"];
+
+const MARKER: &str = "git lfs";
+
+/// What git passes to a pre-push hook: the remote's name (or the url when pushing to one directly), its url
+/// and one update per ref from stdin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrePush {
+  pub remote: String,
+  pub url: String,
+  pub updates: Vec<PushUpdate>,
+}
+
+impl PrePush {
+  /// Parses `<local ref> <local sha> <remote ref> <remote sha>` lines, skipping blank ones.
+  pub fn parse(remote: &str, url: &str, input: &str) -> Result<Self, Error> {
+    let updates = input.lines().filter(|l| !l.trim().is_empty()).map(parse_line).collect::<Result<_, _>>()?;
+    Ok(Self { remote: remote.to_string(), url: url.to_string(), updates })
+  }
+
+  /// The lfs endpoint the pushed url implies.
+  pub fn lfs_url(&self) -> Option<Url> {
+    crate::ext::lfs_url(&self.url)
+  }
+}
+
+fn parse_line(line: &str) -> Result<PushUpdate, Error> {
+  let invalid = || Error::InvalidPushUpdate(line.to_string());
+
+  let [local_ref, local_oid, remote_ref, remote_oid] =
+    line.split_whitespace().collect::<Vec<_>>().try_into().map_err(|_| invalid())?;
+  let local_oid = Oid::from_str(local_oid).map_err(|_| invalid())?;
+  let remote_oid = Oid::from_str(remote_oid).map_err(|_| invalid())?;
+
+  Ok(PushUpdate::new(local_ref, local_oid, remote_ref, remote_oid))
+}
+
+/// Writes the lfs hooks into the repository's hooks directory, honoring `core.hooksPath`. Hooks written
+/// by something else are left alone with [`Error::HookExists`] unless `force` is set.
+pub fn install(repo: &git2::Repository, force: bool) -> Result<Vec<PathBuf>, Error> {
+  let dir = hooks_dir(repo)?;
+  std::fs::create_dir_all(&dir)?;
+
+  let mut installed = Vec::with_capacity(HOOKS.len());
+  for hook in HOOKS {
+    let path = dir.join(hook);
+
+    match std::fs::read_to_string(&path) {
+      Ok(existing) if existing == script(hook, &path) => debug!(hook, "hooks: already installed"),
+      Ok(existing) if !force && !existing.contains(MARKER) => return Err(Error::HookExists(path)),
+      _ => installed.push(path),
+    }
+  }
+
+  // Nothing is written until every hook is known to be safe to replace.
+  for path in installed.iter() {
+    let hook = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    std::fs::write(path, script(hook, path))?;
+    make_executable(path)?;
+  }
+
+  Ok(installed)
+}
+
+fn hooks_dir(repo: &git2::Repository) -> Result<PathBuf, Error> {
+  let path = match repo.config()?.get_path("core.hooksPath") {
+    Ok(path) => path,
+    Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(repo.path().join("hooks")),
+    Err(e) => return Err(e.into()),
+  };
+
+  if path.is_absolute() {
+    return Ok(path);
+  }
+
+  // git runs hooks from the top of the working tree, so a relative path is resolved from there.
+  Ok(repo.workdir().unwrap_or(repo.path()).join(path))
+}
+
+fn script(hook: &str, path: &Path) -> String {
+  // The path ends up inside a double-quoted shell string.
+  let mut quoted = String::new();
+  for c in path.display().to_string().chars() {
+    if matches!(c, '\\' | '"' | '$' | '`') {
+      quoted.push('\\');
+    }
+    quoted.push(c);
+  }
+
+  format!(
+    "#!/bin/sh\n\
+     command -v git-lfs >/dev/null 2>&1 || {{ printf >&2 \"\\n%s\\n\\n\" \"This repository is configured for Git LFS but 'git-lfs' was not found on your path. Remove {quoted} to stop seeing this.\"; exit 2; }}\n\
+     {MARKER} {hook} \"$@\"\n"
+  )
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), Error> {
+  use std::os::unix::fs::PermissionsExt;
+  std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_: &Path) -> Result<(), Error> {
+  Ok(())
+}
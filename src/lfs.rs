@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::collections::HashSet;
-use std::io::BufReader;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use git2::Filter;
 use git2::FilterBuf;
@@ -14,11 +15,16 @@ use crate::Error;
 use tracing::*;
 
 use crate::Pointer;
+use crate::store::FsObjectStore;
+use crate::store::ObjectStore;
 
 #[derive(Default, Clone, Debug)]
 pub struct LfsBuilder {
   exts: Option<HashSet<String>>,
   max_file_size: Option<u64>,
+  object_store: Option<Arc<dyn ObjectStore>>,
+  alternates: Vec<PathBuf>,
+  stores: Arc<Mutex<HashMap<PathBuf, Arc<dyn ObjectStore>>>>,
 }
 
 pub struct Lfs<'a> {
@@ -74,36 +80,47 @@ impl<'a> Lfs<'a> {
   }
 
   fn store_object_if_not_exists(self, pointer: &Pointer, bytes: &[u8]) -> Result<(), Error> {
-    let path = self.object_dir().join(pointer.path());
+    let store = self.object_store()?;
 
-    if path.exists() {
-      debug!(path = %path.display(), "object already exists, skipping");
+    if store.contains(pointer)? {
+      debug!(path = %pointer.path().display(), "object already exists, skipping");
       return Ok(());
     }
 
-    pointer.write_blob_bytes(&self.object_dir(), bytes)?;
-    Ok(())
+    store.put_bytes(pointer, bytes)
   }
 
   fn load_object(self, pointer: &Pointer, out: &mut FilterBuf) -> Result<bool, Error> {
-    let object_dir = self.object_dir();
-    let path = self.object_dir().join(pointer.path());
+    let store = self.object_store()?;
 
-    if !path.exists() {
-      warn!(path = %path.strip_prefix(&object_dir).unwrap_or(&path).display(), "object not found, skipping");
+    if !store.contains(pointer)? {
+      warn!(path = %pointer.path().display(), "object not found, skipping");
       return Ok(false);
     }
 
-    debug!(path = %path.strip_prefix(&object_dir).unwrap_or(&path).display(), "reading lfs object");
+    debug!(path = %pointer.path().display(), "reading lfs object");
 
-    let file = std::fs::File::open(&path)?;
-    let mut reader = BufReader::new(file);
+    let mut reader = store.open(pointer)?;
     std::io::copy(&mut reader, &mut out.as_allocated_vec())?;
     Ok(true)
   }
 
-  fn object_dir(&self) -> PathBuf {
-    self.repo.path().join("lfs/objects")
+  fn object_store(&self) -> Result<Arc<dyn ObjectStore>, Error> {
+    if let Some(store) = &self.config.object_store {
+      return Ok(Arc::clone(store));
+    }
+
+    // The filter runs once per file, so each repository's store is resolved once and reused.
+    let mut stores = self.config.stores.lock().unwrap();
+    if let Some(store) = stores.get(self.repo.path()) {
+      return Ok(Arc::clone(store));
+    }
+
+    let repo = git2::Repository::open(self.repo.path())?;
+    let store: Arc<dyn ObjectStore> =
+      Arc::new(FsObjectStore::for_repo(&repo)?.with_alternates(self.config.alternates.iter().cloned()));
+    stores.insert(self.repo.path().to_path_buf(), Arc::clone(&store));
+    Ok(store)
   }
 }
 
@@ -118,7 +135,23 @@ impl LfsBuilder {
     self
   }
 
+  pub fn with_object_store(mut self, object_store: impl ObjectStore + 'static) -> Self {
+    self.object_store = Some(Arc::new(object_store));
+    self
+  }
+
+  /// Adds alternate object directories to the store resolved for each repository. They can't be combined
+  /// with [`LfsBuilder::with_object_store`], which [`LfsBuilder::install`] rejects.
+  pub fn with_alternates(mut self, alternates: &[&Path]) -> Self {
+    self.alternates.extend(alternates.iter().map(|alternate| alternate.to_path_buf()));
+    self
+  }
+
   pub fn install(self, attributes: &str) -> Result<(), Error> {
+    if self.object_store.is_some() && !self.alternates.is_empty() {
+      return Err(Error::AlternatesWithCustomStore);
+    }
+
     let mut filter = Filter::<()>::new()?;
 
     let config = Arc::new(self);
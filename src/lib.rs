@@ -1,10 +1,23 @@
+pub mod checkout;
 pub mod ext;
+pub mod fetch;
+pub mod fsck;
+pub mod hooks;
+pub mod ls_files;
+pub mod migrate;
+pub mod prune;
+pub mod push;
 pub mod remote;
+pub mod server;
+pub mod status;
+pub mod store;
 
 mod lfs;
 mod pointer;
+mod scan;
 
 pub use pointer::Pointer;
+pub use pointer::PointerCheck;
 
 pub use sha2;
 
@@ -25,6 +38,27 @@ pub enum Error {
   #[error("not a pointer")]
   NotAPointer,
 
+  #[error("the pointer isn't in canonical form")]
+  NonCanonicalPointer,
+
+  #[error("lfs object '{0}' is missing from the local store")]
+  MissingObject(Pointer),
+
+  #[error("invalid pre-push update: '{0}'")]
+  InvalidPushUpdate(String),
+
+  #[error("hook '{}' already exists", .0.display())]
+  HookExists(std::path::PathBuf),
+
+  #[error("alternates can't be used with a custom object store")]
+  AlternatesWithCustomStore,
+
+  #[error("the working tree has uncommitted changes")]
+  DirtyWorktree,
+
+  #[error("object content doesn't match its pointer, expected '{expected}', got '{actual}'")]
+  ObjectMismatch { expected: Pointer, actual: Pointer },
+
   #[error(transparent)]
   Utf8(#[from] std::str::Utf8Error),
 
@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use git2::Delta;
+use git2::ObjectType;
+use git2::Oid;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::scan::INDEX_ENTRY_STAGE_MASK;
+use crate::scan::PointerScanner;
+use crate::store::ObjectStore;
+
+/// Files are found as the iterator is advanced, so stopping early skips the rest of the scan.
+pub type LfsFiles<'a> = Box<dyn Iterator<Item = Result<LfsFile, Error>> + 'a>;
+
+/// Where [`crate::ext::RepoLfsExt::lfs_ls_files`] looks for pointers.
+pub enum LsFilesSource<'a> {
+  Tree(&'a git2::Tree<'a>),
+  Commit(&'a git2::Commit<'a>),
+  Index(&'a git2::Index),
+  /// Files added, modified or deleted between two commits; deleted files are reported with their old blob.
+  Diff(&'a git2::Commit<'a>, &'a git2::Commit<'a>),
+  /// Files in the commit and all of its ancestors, each path and blob reported once.
+  History(&'a git2::Commit<'a>),
+  /// Files in the history of every ref, each path and blob reported once.
+  All,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsFile {
+  pub path: PathBuf,
+  pub pointer: Pointer,
+  pub present: bool,
+  pub blob: Oid,
+  pub deleted: bool,
+}
+
+pub(crate) fn ls_files<'a>(
+  repo: &'a git2::Repository,
+  store: Arc<dyn ObjectStore>,
+  source: LsFilesSource<'a>,
+) -> Result<LfsFiles<'a>, Error> {
+  let mut scanner = PointerScanner::new(repo)?;
+
+  let file = move |scanner: &mut PointerScanner<'_>, path: PathBuf, blob: Oid, deleted: bool| {
+    let pointer = match scanner.pointer(blob) {
+      Ok(Some(pointer)) => pointer,
+      Ok(None) => return None,
+      Err(e) => return Some(Err(e)),
+    };
+
+    Some(store.contains(&pointer).map(|present| LfsFile { path, pointer, present, blob, deleted }))
+  };
+
+  let files: LfsFiles<'a> = match source {
+    LsFilesSource::Tree(tree) => Box::new(TreeFiles::new(repo, scanner, file, vec![Ok(tree.id())], false)),
+    LsFilesSource::Commit(commit) => {
+      Box::new(TreeFiles::new(repo, scanner, file, vec![Ok(commit.tree_id())], false))
+    }
+    LsFilesSource::History(commit) => {
+      let mut revwalk = repo.revwalk()?;
+      revwalk.push(commit.id())?;
+      Box::new(TreeFiles::new(repo, scanner, file, commit_trees(repo, revwalk), true))
+    }
+    LsFilesSource::All => {
+      let mut revwalk = repo.revwalk()?;
+      revwalk.push_glob("*")?;
+      if let Ok(head) = repo.head()
+        && let Some(oid) = head.target()
+      {
+        revwalk.push(oid)?;
+      }
+
+      Box::new(TreeFiles::new(repo, scanner, file, commit_trees(repo, revwalk), true))
+    }
+    LsFilesSource::Index(index) => {
+      Box::new(index.iter().filter(|e| e.flags & INDEX_ENTRY_STAGE_MASK == 0).filter_map(move |entry| {
+        let path = match std::str::from_utf8(&entry.path) {
+          Ok(path) => PathBuf::from(path),
+          Err(e) => return Some(Err(e.into())),
+        };
+
+        file(&mut scanner, path, entry.id, false)
+      }))
+    }
+    LsFilesSource::Diff(old, new) => {
+      let diff = repo.diff_tree_to_tree(Some(&old.tree()?), Some(&new.tree()?), None)?;
+
+      let mut changed = Vec::new();
+      for delta in diff.deltas() {
+        let (file, deleted) = match delta.status() {
+          Delta::Deleted => (delta.old_file(), true),
+          Delta::Unmodified | Delta::Ignored | Delta::Untracked | Delta::Unreadable => continue,
+          _ => (delta.new_file(), false),
+        };
+
+        if let Some(path) = file.path() {
+          changed.push((path.to_path_buf(), file.id(), deleted));
+        }
+      }
+
+      Box::new(
+        changed.into_iter().filter_map(move |(path, blob, deleted)| file(&mut scanner, path, blob, deleted)),
+      )
+    }
+  };
+
+  Ok(files)
+}
+
+type Trees<'a> = Box<dyn Iterator<Item = Result<Oid, Error>> + 'a>;
+
+fn commit_trees<'a>(repo: &'a git2::Repository, revwalk: git2::Revwalk<'a>) -> Trees<'a> {
+  Box::new(revwalk.map(move |commit| Ok(repo.find_commit(commit?)?.tree_id())))
+}
+
+/// Walks trees one entry at a time. Across several trees, subtrees already walked at the same path are skipped
+/// and a path holding the same blob is reported once.
+struct TreeFiles<'a, F> {
+  repo: &'a git2::Repository,
+  scanner: PointerScanner<'a>,
+  file: F,
+  trees: Trees<'a>,
+  stack: Vec<(String, git2::Tree<'a>, usize)>,
+  dedup: bool,
+  seen_trees: HashSet<(String, Oid)>,
+  seen_files: HashSet<(String, Oid)>,
+}
+
+impl<'a, F> TreeFiles<'a, F> {
+  fn new(
+    repo: &'a git2::Repository,
+    scanner: PointerScanner<'a>,
+    file: F,
+    trees: impl IntoIterator<Item = Result<Oid, Error>, IntoIter: 'a>,
+    dedup: bool,
+  ) -> Self {
+    Self {
+      repo,
+      scanner,
+      file,
+      trees: Box::new(trees.into_iter()),
+      stack: Vec::new(),
+      dedup,
+      seen_trees: HashSet::new(),
+      seen_files: HashSet::new(),
+    }
+  }
+
+  fn push_tree(&mut self, prefix: String, oid: Oid) -> Result<(), Error> {
+    if self.dedup && !self.seen_trees.insert((prefix.clone(), oid)) {
+      return Ok(());
+    }
+
+    self.stack.push((prefix, self.repo.find_tree(oid)?, 0));
+    Ok(())
+  }
+}
+
+impl<'a, F> Iterator for TreeFiles<'a, F>
+where
+  F: FnMut(&mut PointerScanner<'a>, PathBuf, Oid, bool) -> Option<Result<LfsFile, Error>>,
+{
+  type Item = Result<LfsFile, Error>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let Some((prefix, tree, next)) = self.stack.last_mut() else {
+        let pushed = self.trees.next()?.and_then(|oid| self.push_tree(String::new(), oid));
+        if let Err(e) = pushed {
+          return Some(Err(e));
+        }
+
+        continue;
+      };
+
+      let Some(entry) = tree.get(*next) else {
+        self.stack.pop();
+        continue;
+      };
+      *next += 1;
+
+      let Ok(name) = std::str::from_utf8(entry.name_bytes()) else {
+        warn!(entry = %entry.id(), prefix = %prefix, "ls-files: skipping entry with a non-utf8 name");
+        continue;
+      };
+
+      let path = format!("{}{}", prefix, name);
+      let (oid, kind) = (entry.id(), entry.kind());
+      drop(entry);
+
+      match kind {
+        Some(ObjectType::Tree) => {
+          if let Err(e) = self.push_tree(format!("{}/", path), oid) {
+            return Some(Err(e));
+          }
+        }
+        Some(ObjectType::Blob) => {
+          if self.dedup && !self.seen_files.insert((path.clone(), oid)) {
+            continue;
+          }
+
+          if let Some(file) = (self.file)(&mut self.scanner, PathBuf::from(path), oid, false) {
+            return Some(file);
+          }
+        }
+        _ => (),
+      }
+    }
+  }
+}
@@ -0,0 +1,563 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use git2::FileMode;
+use git2::ObjectType;
+use git2::Oid;
+use git2::Sort;
+use git2::build::CheckoutBuilder;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::scan::PathFilter;
+use crate::scan::PointerScanner;
+use crate::store::ObjectStore;
+
+const ATTRIBUTES: &str = ".gitattributes";
+const LFS_ATTRIBUTES: &str = "filter=lfs diff=lfs merge=lfs -text";
+const INFO_DEFAULT_ABOVE: u64 = 1024 * 1024;
+
+#[derive(Debug, Default, Clone)]
+pub struct MigrateOptions {
+  refs: Vec<String>,
+  include: Vec<String>,
+  exclude: Vec<String>,
+  above: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MigrateReport {
+  /// Every rewritten commit, keyed by its original id. Commits that didn't change aren't listed.
+  pub commits: HashMap<Oid, Oid>,
+  pub refs: Vec<RefUpdate>,
+  /// Number of distinct blobs that were converted.
+  pub blobs: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RefUpdate {
+  pub name: String,
+  pub old: Oid,
+  pub new: Oid,
+}
+
+/// Blob sizes across the history of the selected refs, each distinct blob counted once.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrateInfo {
+  pub blobs: usize,
+  pub total_size: u64,
+  pub pointers: usize,
+  /// Blobs grouped by extension as `*.ext`, or by file name for files without one.
+  pub extensions: Vec<SizeGroup>,
+  /// Blobs grouped by their top-level directory only, `/` for files at the root.
+  pub prefixes: Vec<SizeGroup>,
+  /// Extensions with a blob above the threshold, suitable for [`crate::LfsBuilder::with_file_extensions`].
+  pub recommended_extensions: Vec<String>,
+  /// `.gitattributes` patterns covering every blob above the threshold.
+  pub recommended_patterns: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeGroup {
+  pub name: String,
+  pub blobs: usize,
+  pub total_size: u64,
+  pub largest_size: u64,
+  pub largest_path: String,
+  pub pointers: usize,
+}
+
+impl MigrateOptions {
+  /// Refs to rewrite along with their history; `HEAD` when none are given.
+  pub fn with_refs(mut self, refs: &[&str]) -> Self {
+    self.refs = refs.iter().map(|r| r.to_string()).collect();
+    self
+  }
+
+  pub fn with_include(mut self, include: &[&str]) -> Self {
+    self.include = include.iter().map(|p| p.to_string()).collect();
+    self
+  }
+
+  pub fn with_exclude(mut self, exclude: &[&str]) -> Self {
+    self.exclude = exclude.iter().map(|p| p.to_string()).collect();
+    self
+  }
+
+  /// Only convert blobs bigger than `size` bytes. For `migrate info`, the size above which a path is
+  /// recommended for lfs, 1 MiB by default.
+  pub fn with_above(mut self, size: u64) -> Self {
+    self.above = Some(size);
+    self
+  }
+}
+
+pub(crate) fn import(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  options: &MigrateOptions,
+) -> Result<MigrateReport, Error> {
+  let mut scanner = PointerScanner::new(repo)?;
+
+  let mut convert = |blob: Oid| -> Result<Option<Oid>, Error> {
+    let (size, _) = scanner.header(blob)?;
+    if size == 0
+      || options.above.is_some_and(|above| size as u64 <= above)
+      || scanner.pointer(blob)?.is_some()
+    {
+      return Ok(None);
+    }
+
+    let blob = repo.find_blob(blob)?;
+    let pointer = Pointer::from_blob_bytes(blob.content())?;
+    if !store.contains(&pointer)? {
+      store.put_bytes(&pointer, blob.content())?;
+    }
+
+    debug!(blob = %blob.id(), pointer = %pointer, "migrate import: converted blob");
+    Ok(Some(repo.blob(&pointer.as_bytes()?)?))
+  };
+
+  let attributes = |existing: Option<&[u8]>, converted: &[String]| -> Option<Vec<u8>> {
+    if converted.is_empty() {
+      return None;
+    }
+
+    let lines = if options.include.is_empty() {
+      converted.iter().map(|path| format!("/{} {}", escape_pattern(path), LFS_ATTRIBUTES)).collect::<Vec<_>>()
+    } else {
+      options.include.iter().map(|pattern| format!("{} {}", pattern, LFS_ATTRIBUTES)).collect()
+    };
+
+    let existing = String::from_utf8_lossy(existing.unwrap_or_default()).into_owned();
+    let missing =
+      lines.iter().filter(|line| !existing.lines().any(|l| l.trim() == line.as_str())).collect::<Vec<_>>();
+    if missing.is_empty() {
+      return None;
+    }
+
+    let mut content = existing;
+    if !content.is_empty() && !content.ends_with('\n') {
+      content.push('\n');
+    }
+
+    for line in missing {
+      content.push_str(line);
+      content.push('\n');
+    }
+
+    Some(content.into_bytes())
+  };
+
+  let mut rewriter = Rewriter::new(repo, options, &mut convert)?;
+  let report = rewrite_history(repo, options, &mut rewriter, &attributes)?;
+
+  info!(commits = report.commits.len(), blobs = report.blobs, "migrate import: done");
+  Ok(report)
+}
+
+pub(crate) fn export(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  options: &MigrateOptions,
+) -> Result<MigrateReport, Error> {
+  let mut scanner = PointerScanner::new(repo)?;
+
+  let mut convert = |blob: Oid| -> Result<Option<Oid>, Error> {
+    let Some(pointer) = scanner.pointer(blob)? else {
+      return Ok(None);
+    };
+
+    if !store.contains(&pointer)? {
+      return Err(Error::MissingObject(pointer));
+    }
+
+    debug!(blob = %blob, pointer = %pointer, "migrate export: inlined lfs object");
+    Ok(Some(repo.blob(&store.read_to_vec(&pointer)?)?))
+  };
+
+  let attributes = |existing: Option<&[u8]>, _: &[String]| -> Option<Vec<u8>> {
+    let existing = String::from_utf8_lossy(existing?).into_owned();
+    let is_exported = |line: &str| {
+      let mut parts = line.split_whitespace();
+      let pattern = parts.next().unwrap_or_default();
+      parts.any(|attr| attr == "filter=lfs")
+        && (options.include.is_empty() || options.include.iter().any(|p| p == pattern))
+    };
+
+    if !existing.lines().any(is_exported) {
+      return None;
+    }
+
+    let kept = existing.lines().filter(|line| !is_exported(line)).collect::<Vec<_>>();
+    let content = if kept.is_empty() { String::new() } else { format!("{}\n", kept.join("\n")) };
+    Some(content.into_bytes())
+  };
+
+  let mut rewriter = Rewriter::new(repo, options, &mut convert)?;
+  let report = rewrite_history(repo, options, &mut rewriter, &attributes)?;
+
+  info!(commits = report.commits.len(), blobs = report.blobs, "migrate export: done");
+  Ok(report)
+}
+
+/// Pointers at paths `export` would rewrite whose objects aren't in the store yet.
+pub(crate) fn export_missing(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  options: &MigrateOptions,
+) -> Result<Vec<Pointer>, Error> {
+  let filter = PathFilter::new(&options.include, &options.exclude)?;
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut pointers = HashSet::new();
+
+  let mut revwalk = repo.revwalk()?;
+  for (_, tip) in resolve_refs(repo, &options.refs)? {
+    revwalk.push(tip)?;
+  }
+
+  for commit in revwalk {
+    let tree = repo.find_commit(commit?)?.tree()?;
+    scanner.walk_tree(&tree, |path, _, pointer| {
+      if let Some(pointer) = pointer
+        && filter.matches(Path::new(path))
+      {
+        pointers.insert(pointer);
+      }
+    })?;
+  }
+
+  let mut missing = Vec::new();
+  for pointer in pointers {
+    if !store.contains(&pointer)? {
+      missing.push(pointer);
+    }
+  }
+
+  Ok(missing)
+}
+
+pub(crate) fn info(repo: &git2::Repository, options: &MigrateOptions) -> Result<MigrateInfo, Error> {
+  let filter = PathFilter::new(&options.include, &options.exclude)?;
+  let above = options.above.unwrap_or(INFO_DEFAULT_ABOVE);
+
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut seen = HashSet::new();
+  let mut blobs = Vec::new();
+
+  let mut revwalk = repo.revwalk()?;
+  for (_, tip) in resolve_refs(repo, &options.refs)? {
+    revwalk.push(tip)?;
+  }
+
+  for commit in revwalk {
+    let tree = repo.find_commit(commit?)?.tree()?;
+    scanner.walk_tree(&tree, |path, blob, pointer| {
+      if seen.insert(blob) && filter.matches(Path::new(path)) {
+        blobs.push((path.to_string(), blob, pointer.is_some()));
+      }
+    })?;
+  }
+
+  let mut info = MigrateInfo::default();
+  let mut extensions = HashMap::<String, SizeGroup>::new();
+  let mut prefixes = HashMap::<String, SizeGroup>::new();
+  let mut patterns = Vec::new();
+
+  for (path, blob, is_pointer) in blobs {
+    let size = scanner.header(blob)?.0 as u64;
+    let extension = Path::new(&path).extension().map(|ext| ext.to_string_lossy().into_owned());
+    let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+    let prefix = path.split_once('/').map(|(dir, _)| dir.to_string()).unwrap_or_else(|| "/".to_string());
+
+    info.blobs += 1;
+    info.total_size += size;
+    info.pointers += is_pointer as usize;
+
+    let extension_key = extension.as_ref().map(|ext| format!("*.{}", ext)).unwrap_or_else(|| name.clone());
+    for (groups, key) in [(&mut extensions, extension_key), (&mut prefixes, prefix)] {
+      let group = groups.entry(key.clone()).or_insert_with(|| SizeGroup { name: key, ..Default::default() });
+      group.blobs += 1;
+      group.total_size += size;
+      group.pointers += is_pointer as usize;
+      if size > group.largest_size {
+        group.largest_size = size;
+        group.largest_path = path.clone();
+      }
+    }
+
+    if is_pointer || size <= above {
+      continue;
+    }
+
+    match extension {
+      Some(ext) if !info.recommended_extensions.contains(&ext) => {
+        patterns.push(format!("*.{}", ext));
+        info.recommended_extensions.push(ext);
+      }
+      Some(_) => (),
+      None => {
+        let pattern = escape_pattern(&name);
+        if !patterns.contains(&pattern) {
+          patterns.push(pattern);
+        }
+      }
+    }
+  }
+
+  let by_size = |groups: HashMap<String, SizeGroup>| {
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    groups.sort_by(|a, b| b.total_size.cmp(&a.total_size).then_with(|| a.name.cmp(&b.name)));
+    groups
+  };
+
+  info.extensions = by_size(extensions);
+  info.prefixes = by_size(prefixes);
+  info.recommended_extensions.sort();
+  patterns.sort();
+  info.recommended_patterns = patterns;
+
+  Ok(info)
+}
+
+/// Escapes a path so it only matches itself as a `.gitattributes` pattern.
+fn escape_pattern(path: &str) -> String {
+  let mut escaped = String::with_capacity(path.len());
+  for c in path.chars() {
+    match c {
+      ' ' => escaped.push_str("[[:space:]]"),
+      '*' | '?' | '[' | '\\' | '#' | '!' => {
+        escaped.push('\\');
+        escaped.push(c);
+      }
+      c => escaped.push(c),
+    }
+  }
+
+  escaped
+}
+
+type Convert<'a> = dyn FnMut(Oid) -> Result<Option<Oid>, Error> + 'a;
+type Attributes<'a> = dyn Fn(Option<&[u8]>, &[String]) -> Option<Vec<u8>> + 'a;
+
+/// Rewrites trees bottom-up, replacing blobs at matching paths with whatever `convert` returns for them.
+struct Rewriter<'r, 'c> {
+  repo: &'r git2::Repository,
+  filter: PathFilter,
+  convert: &'c mut Convert<'c>,
+  blobs: HashMap<Oid, Option<Oid>>,
+  trees: HashMap<(Oid, String), (Oid, Vec<String>)>,
+}
+
+impl<'r, 'c> Rewriter<'r, 'c> {
+  fn new(
+    repo: &'r git2::Repository,
+    options: &MigrateOptions,
+    convert: &'c mut Convert<'c>,
+  ) -> Result<Self, Error> {
+    let filter = PathFilter::new(&options.include, &options.exclude)?;
+    Ok(Self { repo, filter, convert, blobs: HashMap::new(), trees: HashMap::new() })
+  }
+
+  /// Returns the new tree id and the paths of blobs that were replaced in it.
+  fn rewrite_tree(&mut self, tree_id: Oid, prefix: &str) -> Result<(Oid, Vec<String>), Error> {
+    let key = (tree_id, prefix.to_string());
+    if let Some(rewritten) = self.trees.get(&key) {
+      return Ok(rewritten.clone());
+    }
+
+    let tree = self.repo.find_tree(tree_id)?;
+    let mut builder = self.repo.treebuilder(Some(&tree))?;
+    let mut converted = Vec::new();
+    let mut changed = false;
+
+    for entry in tree.iter() {
+      let name = entry.name().unwrap_or_default();
+      if name.is_empty() || name == ATTRIBUTES {
+        continue;
+      }
+
+      let path = format!("{}{}", prefix, name);
+
+      match entry.kind() {
+        Some(ObjectType::Tree) => {
+          let (new, paths) = self.rewrite_tree(entry.id(), &format!("{}/", path))?;
+          if new != entry.id() {
+            builder.insert(name, new, entry.filemode())?;
+            changed = true;
+          }
+
+          converted.extend(paths);
+        }
+        Some(ObjectType::Blob)
+          if entry.filemode() != i32::from(FileMode::Link) && self.filter.matches(Path::new(&path)) =>
+        {
+          let new = match self.blobs.get(&entry.id()) {
+            Some(new) => *new,
+            None => {
+              let new = (self.convert)(entry.id())?;
+              self.blobs.insert(entry.id(), new);
+              new
+            }
+          };
+
+          if let Some(new) = new {
+            builder.insert(name, new, entry.filemode())?;
+            converted.push(path);
+            changed = true;
+          }
+        }
+        _ => (),
+      }
+    }
+
+    let new_id = if changed { builder.write()? } else { tree_id };
+    self.trees.insert(key, (new_id, converted.clone()));
+    Ok((new_id, converted))
+  }
+}
+
+fn rewrite_history(
+  repo: &git2::Repository,
+  options: &MigrateOptions,
+  rewriter: &mut Rewriter<'_, '_>,
+  attributes: &Attributes<'_>,
+) -> Result<MigrateReport, Error> {
+  let refs = resolve_refs(repo, &options.refs)?;
+  let head = repo.head().ok().map(|head| head.name().map(|name| name.to_string()).unwrap_or_default());
+
+  // The checked out branch gets its index and `.gitattributes` replaced, so don't run over local changes.
+  let rewrites_head = head.as_ref().is_some_and(|head| refs.iter().any(|(name, _)| name == head));
+  if rewrites_head && !repo.is_bare() && is_dirty(repo)? {
+    return Err(Error::DirtyWorktree);
+  }
+
+  let mut report = MigrateReport::default();
+
+  let mut revwalk = repo.revwalk()?;
+  revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+  for (_, tip) in refs.iter() {
+    revwalk.push(*tip)?;
+  }
+
+  for commit_id in revwalk {
+    let commit = repo.find_commit(commit_id?)?;
+
+    let (tree_id, converted) = rewriter.rewrite_tree(commit.tree_id(), "")?;
+    let tree_id = update_attributes(repo, tree_id, &converted, attributes)?;
+
+    let parent_ids =
+      commit.parent_ids().map(|p| report.commits.get(&p).copied().unwrap_or(p)).collect::<Vec<_>>();
+
+    if tree_id == commit.tree_id() && parent_ids == commit.parent_ids().collect::<Vec<_>>() {
+      continue;
+    }
+
+    if commit.header_field_bytes("gpgsig").is_ok() {
+      warn!(commit = %commit.id(), "migrate: the rewritten commit loses its signature");
+    }
+    if commit.header_field_bytes("encoding").is_ok() {
+      warn!(commit = %commit.id(), "migrate: the rewritten commit loses its encoding header");
+    }
+
+    let parents = parent_ids.iter().map(|p| repo.find_commit(*p)).collect::<Result<Vec<_>, _>>()?;
+    let new_id = repo.commit(
+      None,
+      &commit.author(),
+      &commit.committer(),
+      commit.message_raw().unwrap_or_default(),
+      &repo.find_tree(tree_id)?,
+      &parents.iter().collect::<Vec<_>>(),
+    )?;
+
+    debug!(old = %commit.id(), new = %new_id, "migrate: rewrote commit");
+    report.commits.insert(commit.id(), new_id);
+  }
+
+  for (name, old) in refs {
+    let Some(new) = report.commits.get(&old).copied() else {
+      continue;
+    };
+
+    let mut reference = repo.find_reference(&name)?;
+    if reference.target() != Some(old) {
+      warn!(reference = %name, "migrate: reference doesn't point directly to a commit, not updating it");
+      continue;
+    }
+
+    reference.set_target(new, "lfs migrate")?;
+    report.refs.push(RefUpdate { name: name.clone(), old, new });
+
+    if head.as_deref() == Some(name.as_str()) && !repo.is_bare() {
+      let tree = repo.find_commit(new)?.tree()?;
+
+      let mut checkout = CheckoutBuilder::new();
+      checkout.path(ATTRIBUTES).force();
+      repo.checkout_tree(tree.as_object(), Some(&mut checkout))?;
+
+      let mut index = repo.index()?;
+      index.read_tree(&tree)?;
+      index.write()?;
+    }
+  }
+
+  report.blobs = rewriter.blobs.values().filter(|new| new.is_some()).count();
+  Ok(report)
+}
+
+fn is_dirty(repo: &git2::Repository) -> Result<bool, Error> {
+  let mut options = git2::StatusOptions::new();
+  options.include_untracked(false).include_ignored(false);
+
+  Ok(repo.statuses(Some(&mut options))?.iter().any(|entry| entry.status() != git2::Status::CURRENT))
+}
+
+fn update_attributes(
+  repo: &git2::Repository,
+  tree_id: Oid,
+  converted: &[String],
+  attributes: &Attributes<'_>,
+) -> Result<Oid, Error> {
+  let tree = repo.find_tree(tree_id)?;
+  let existing = match tree.get_name(ATTRIBUTES) {
+    Some(entry) => Some(repo.find_blob(entry.id())?),
+    None => None,
+  };
+
+  let Some(content) = attributes(existing.as_ref().map(|blob| blob.content()), converted) else {
+    return Ok(tree_id);
+  };
+
+  let mut builder = repo.treebuilder(Some(&tree))?;
+  if content.is_empty() {
+    builder.remove(ATTRIBUTES)?;
+  } else {
+    builder.insert(ATTRIBUTES, repo.blob(&content)?, FileMode::Blob.into())?;
+  }
+
+  Ok(builder.write()?)
+}
+
+/// Resolves ref names to full names and the commits they point to. `HEAD` resolves to the branch it's on.
+fn resolve_refs(repo: &git2::Repository, refs: &[String]) -> Result<Vec<(String, Oid)>, Error> {
+  let refs = if refs.is_empty() { vec!["HEAD".to_string()] } else { refs.to_vec() };
+
+  let mut resolved = Vec::with_capacity(refs.len());
+  for name in refs.iter() {
+    let reference = match name.as_str() {
+      "HEAD" => repo.head()?,
+      name => repo.resolve_reference_from_short_name(name)?,
+    };
+
+    let full_name = reference.name().unwrap_or(name).to_string();
+    resolved.push((full_name, reference.peel_to_commit()?.id()));
+  }
+
+  Ok(resolved)
+}
@@ -1,11 +1,12 @@
 use std::fmt::Display;
 
-use std::io::BufWriter;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
 
+use serde::Deserialize;
+use serde::Serialize;
 use sha2::Digest;
 
 use crate::Error;
@@ -39,6 +40,61 @@ impl std::fmt::Debug for Pointer {
   }
 }
 
+/// The `{ oid, size }` shape used by the batch API.
+#[derive(Serialize, Deserialize)]
+struct PointerRepr {
+  oid: String,
+  size: usize,
+}
+
+impl Serialize for Pointer {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    PointerRepr { oid: self.hex(), size: self.size }.serialize(serializer)
+  }
+}
+
+impl<'de> Deserialize<'de> for Pointer {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    let repr = PointerRepr::deserialize(deserializer)?;
+    let mut hash = [0; HASH_LEN];
+    hex::decode_to_slice(&repr.oid, &mut hash).map_err(serde::de::Error::custom)?;
+    Ok(Pointer { hash, size: repr.size })
+  }
+}
+
+/// What `git lfs pointer` finds when checking some input against the pointer a file should have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerCheck {
+  /// The input is a pointer, and the file's one when there was a file to compare with.
+  Match(Pointer),
+  Mismatch {
+    expected: Pointer,
+    actual: Pointer,
+  },
+  /// The input isn't a pointer; holds why it was rejected.
+  Invalid(String),
+}
+
+impl PointerCheck {
+  /// Parses `input` as a pointer, comparing it with `file` when given. With `strict`, only the exact
+  /// encoding [`Pointer::write_pointer`] produces is accepted.
+  pub fn new(file: Option<&Pointer>, input: &[u8], strict: bool) -> Self {
+    let actual = match Pointer::check(input, strict) {
+      Ok(pointer) => pointer,
+      Err(e) => return Self::Invalid(e.to_string()),
+    };
+
+    match file {
+      Some(expected) if *expected != actual => Self::Mismatch { expected: *expected, actual },
+      _ => Self::Match(actual),
+    }
+  }
+
+  pub fn is_match(&self) -> bool {
+    matches!(self, Self::Match(_))
+  }
+}
+
 impl Pointer {
   pub fn from_parts(hash: &[u8], size: usize) -> Self {
     let mut copied_hash = [0; HASH_LEN];
@@ -61,6 +117,17 @@ impl Pointer {
     Ok(Self { hash, size: bytes.len() })
   }
 
+  /// Parses arbitrary bytes as a pointer. Unlike [`Pointer::from_str`], `strict` also rejects pointers that
+  /// aren't byte for byte what [`Pointer::write_pointer`] would write.
+  pub fn check(bytes: &[u8], strict: bool) -> Result<Self, Error> {
+    let pointer = Pointer::from_str(std::str::from_utf8(bytes)?)?;
+    if strict && pointer.as_bytes()? != bytes {
+      return Err(Error::NonCanonicalPointer);
+    }
+
+    Ok(pointer)
+  }
+
   pub fn size(&self) -> usize {
     self.size
   }
@@ -102,17 +169,6 @@ impl Pointer {
     Ok(bytes)
   }
 
-  pub fn write_blob_bytes(&self, absolute_object_dir: &Path, bytes: &[u8]) -> Result<(), Error> {
-    let file = absolute_object_dir.join(self.path());
-    std::fs::create_dir_all(file.parent().unwrap())?;
-
-    info!(path = %file.display(), "writing lfs object");
-
-    let mut file = std::fs::File::options().create_new(true).write(true).open(&file)?;
-    BufWriter::new(&mut file).write_all(bytes)?;
-    Ok(())
-  }
-
   pub fn from_str_short(bytes: &[u8]) -> Option<Self> {
     match bytes.get(..(bytes.len().min(POINTER_ROUGH_LEN.end))).map(str::from_utf8) {
       Some(Ok(text)) => {
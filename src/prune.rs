@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::scan::PointerScanner;
+use crate::scan::recent_commits;
+use crate::scan::recent_ref_tips;
+use crate::store::ObjectStore;
+
+#[derive(Debug, Clone)]
+pub struct PruneOptions {
+  retain_refs: Vec<String>,
+  recent_refs_days: u32,
+  recent_commits_days: u32,
+  remote: String,
+  dry_run: bool,
+}
+
+/// Local objects that no retained commit references. Nothing is deleted until the plan is applied with
+/// [`crate::remote::LfsClient::prune`], which keeps every object the remote can't confirm it has.
+#[derive(Debug, Clone)]
+pub struct PrunePlan {
+  pub candidates: Vec<Pointer>,
+  pub retained: usize,
+  pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PruneReport {
+  pub deleted: Vec<Pointer>,
+  pub unverified: Vec<Pointer>,
+  pub bytes_reclaimed: u64,
+  pub dry_run: bool,
+}
+
+impl Default for PruneOptions {
+  fn default() -> Self {
+    Self {
+      retain_refs: Vec::new(),
+      recent_refs_days: 7,
+      recent_commits_days: 0,
+      remote: "origin".to_string(),
+      dry_run: false,
+    }
+  }
+}
+
+impl PruneOptions {
+  pub fn with_retain_refs(mut self, refs: &[&str]) -> Self {
+    self.retain_refs = refs.iter().map(|r| r.to_string()).collect();
+    self
+  }
+
+  /// Branches and remote-tracking refs whose tip was committed within `days` are kept.
+  pub fn with_recent_refs_days(mut self, days: u32) -> Self {
+    self.recent_refs_days = days;
+    self
+  }
+
+  /// Commits made within `days` of a retained tip are kept as well as the tip itself.
+  pub fn with_recent_commits_days(mut self, days: u32) -> Self {
+    self.recent_commits_days = days;
+    self
+  }
+
+  /// Commits not reachable from this remote's tracking refs are treated as unpushed and kept.
+  pub fn with_remote(mut self, remote: &str) -> Self {
+    self.remote = remote.to_string();
+    self
+  }
+
+  pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+    self.dry_run = dry_run;
+    self
+  }
+}
+
+impl PrunePlan {
+  pub fn bytes(&self) -> u64 {
+    self.candidates.iter().map(|p| p.size() as u64).sum()
+  }
+
+  pub(crate) fn apply(
+    &self,
+    store: &dyn ObjectStore,
+    verified: &HashSet<Pointer>,
+  ) -> Result<PruneReport, Error> {
+    let mut report = PruneReport { dry_run: self.dry_run, ..Default::default() };
+
+    for pointer in self.candidates.iter() {
+      if !verified.contains(pointer) {
+        debug!(pointer = %pointer, "prune: object isn't verified on the remote, keeping");
+        report.unverified.push(*pointer);
+        continue;
+      }
+
+      if !self.dry_run {
+        store.remove(pointer)?;
+      }
+
+      report.bytes_reclaimed += pointer.size() as u64;
+      report.deleted.push(*pointer);
+    }
+
+    info!(
+      deleted = report.deleted.len(),
+      unverified = report.unverified.len(),
+      bytes = report.bytes_reclaimed,
+      dry_run = self.dry_run,
+      "prune: done"
+    );
+
+    Ok(report)
+  }
+}
+
+pub(crate) fn plan(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  options: &PruneOptions,
+) -> Result<PrunePlan, Error> {
+  let retained = retained_pointers(repo, options)?;
+  let retained_hashes = retained.iter().map(|p| *p.hash()).collect::<HashSet<_>>();
+
+  let mut candidates = Vec::new();
+  for object in store.iter()? {
+    let object = object?;
+    if !retained_hashes.contains(object.hash()) {
+      candidates.push(object);
+    }
+  }
+
+  debug!(retained = retained.len(), candidates = candidates.len(), "prune: planned");
+  Ok(PrunePlan { candidates, retained: retained.len(), dry_run: options.dry_run })
+}
+
+fn retained_pointers(repo: &git2::Repository, options: &PruneOptions) -> Result<HashSet<Pointer>, Error> {
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut pointers = HashSet::new();
+  let mut heads = Vec::new();
+
+  // Worktrees share the object store, so what any of them has checked out or staged is kept.
+  for checkout in checkouts(repo)? {
+    match checkout.head() {
+      Ok(head) => heads.push(head.peel_to_commit()?.id()),
+      Err(e) if matches!(e.code(), git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound) => (),
+      Err(e) => return Err(e.into()),
+    }
+
+    if !checkout.is_bare() {
+      for entry in checkout.index()?.iter() {
+        if let Ok(Some(pointer)) = scanner.pointer(entry.id) {
+          pointers.insert(pointer);
+        }
+      }
+    }
+  }
+
+  let mut tips = heads.clone();
+
+  for r in options.retain_refs.iter() {
+    tips.push(repo.revparse_single(r)?.peel_to_commit()?.id());
+  }
+
+  tips.extend(recent_ref_tips(repo, options.recent_refs_days)?);
+
+  for tip in tips.iter() {
+    for commit in recent_commits(repo, *tip, options.recent_commits_days)? {
+      scanner.collect_tree(&repo.find_commit(commit)?.tree()?, &mut pointers)?;
+    }
+  }
+
+  let mut unpushed = repo.revwalk()?;
+  unpushed.push_glob("refs/heads/*")?;
+  for head in heads {
+    unpushed.push(head)?;
+  }
+  unpushed.hide_glob(&format!("refs/remotes/{}/*", options.remote))?;
+
+  for commit in unpushed {
+    let commit = repo.find_commit(commit?)?;
+    debug!(commit = %commit.id(), "prune: retaining unpushed commit");
+    scanner.collect_tree(&commit.tree()?, &mut pointers)?;
+  }
+
+  Ok(pointers)
+}
+
+/// This repository, the main worktree if it is a linked one, and every linked worktree that still exists.
+fn checkouts(repo: &git2::Repository) -> Result<Vec<git2::Repository>, Error> {
+  let mut checkouts = vec![git2::Repository::open(repo.path())?];
+  if repo.is_worktree() {
+    checkouts.push(git2::Repository::open(repo.commondir())?);
+  }
+
+  let names = repo.worktrees()?;
+  for name in names.iter_bytes().filter_map(|name| std::str::from_utf8(name).ok()) {
+    let worktree = repo.find_worktree(name)?;
+    if let Err(e) = worktree.validate() {
+      debug!(worktree = name, error = %e, "prune: skipping missing worktree");
+      continue;
+    }
+
+    checkouts.push(git2::Repository::open_from_worktree(&worktree)?);
+  }
+
+  Ok(checkouts)
+}
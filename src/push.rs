@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use git2::Oid;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::scan::PointerScanner;
+
+/// One ref update of a push, as a pre-push hook receives it. A zero `local_oid` deletes `remote_ref`; a zero
+/// `remote_oid` creates it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushUpdate {
+  pub local_ref: String,
+  pub local_oid: Oid,
+  pub remote_ref: String,
+  pub remote_oid: Oid,
+}
+
+impl PushUpdate {
+  pub fn new(local_ref: &str, local_oid: Oid, remote_ref: &str, remote_oid: Oid) -> Self {
+    Self { local_ref: local_ref.to_string(), local_oid, remote_ref: remote_ref.to_string(), remote_oid }
+  }
+
+  pub fn is_delete(&self) -> bool {
+    self.local_oid.is_zero()
+  }
+}
+
+/// Pointers in commits the updates push that aren't in the commits the remote refs currently point to or
+/// in the remote-tracking commits the pushed ones are based on.
+pub(crate) fn objects_to_push(
+  repo: &git2::Repository,
+  updates: &[PushUpdate],
+) -> Result<Vec<Pointer>, Error> {
+  let mut scanner = PointerScanner::new(repo)?;
+  let mut revwalk = repo.revwalk()?;
+  let mut pushed = HashSet::new();
+  let mut objects = HashSet::new();
+
+  for update in updates.iter().filter(|u| !u.is_delete()) {
+    revwalk.push(repo.find_object(update.local_oid, None)?.peel_to_commit()?.id())?;
+
+    if update.remote_oid.is_zero() {
+      continue;
+    }
+
+    // The remote ref may point to a commit we've never fetched; there's nothing to hide then.
+    match repo.find_object(update.remote_oid, None).and_then(|o| o.peel_to_commit()) {
+      Ok(remote) => {
+        revwalk.hide(remote.id())?;
+        // Objects in the remote's tree are already there; collecting it first also lets the scanner skip
+        // every subtree the local commits share with it.
+        scanner.collect_tree(&remote.tree()?, &mut pushed)?;
+      }
+      Err(e) => {
+        debug!(remote_ref = %update.remote_ref, error = %e, "push: remote commit isn't available locally")
+      }
+    }
+  }
+
+  revwalk.hide_glob("refs/remotes/*")?;
+  let commits = revwalk.map(|c| repo.find_commit(c?)).collect::<Result<Vec<_>, _>>()?;
+  let walked = commits.iter().map(|c| c.id()).collect::<HashSet<_>>();
+
+  // Parents outside the walk are hidden ones the remote already has, so neither are their objects new.
+  for parent in commits.iter().flat_map(|c| c.parents()).filter(|p| !walked.contains(&p.id())) {
+    scanner.collect_tree(&parent.tree()?, &mut pushed)?;
+  }
+
+  for commit in commits.iter() {
+    scanner.collect_tree(&commit.tree()?, &mut objects)?;
+  }
+
+  Ok(objects.difference(&pushed).copied().collect())
+}
@@ -0,0 +1,471 @@
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+
+use futures::StreamExt;
+use futures::channel::mpsc::UnboundedReceiver;
+use futures::channel::mpsc::UnboundedSender;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::remote::BatchResponse;
+use crate::remote::LfsClient;
+use crate::remote::LfsRemote;
+use crate::remote::ObjectAction;
+use crate::remote::ObjectError;
+use crate::remote::Progress;
+use crate::remote::ProgressEvent;
+use crate::remote::RemoteError;
+use crate::remote::unexpected_object;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDirection {
+  Download,
+  Upload,
+  Both,
+}
+
+/// An external process speaking the git-lfs custom transfer protocol, offered to the server by name in
+/// batch requests.
+#[derive(Debug, Clone)]
+pub struct CustomTransfer {
+  name: String,
+  path: String,
+  args: Vec<String>,
+  direction: TransferDirection,
+  remote: String,
+}
+
+impl CustomTransfer {
+  pub fn new(name: &str, path: &str) -> Self {
+    Self {
+      name: name.to_string(),
+      path: path.to_string(),
+      args: Vec::new(),
+      direction: TransferDirection::Both,
+      remote: "origin".to_string(),
+    }
+  }
+
+  /// Reads `lfs.customtransfer.<name>.path`, `.args` and `.direction`; `None` if the agent has no path.
+  pub fn from_config(config: &git2::Config, name: &str) -> Result<Option<Self>, Error> {
+    let key = |field: &str| format!("lfs.customtransfer.{}.{}", name, field);
+
+    let path = match config.get_string(&key("path")) {
+      Ok(path) => path,
+      Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+      Err(e) => return Err(e.into()),
+    };
+
+    let mut transfer = Self::new(name, &path);
+    if let Ok(args) = config.get_string(&key("args")) {
+      transfer.args = args.split_whitespace().map(str::to_string).collect();
+    }
+
+    transfer.direction = match config.get_string(&key("direction")).as_deref() {
+      Ok("download") => TransferDirection::Download,
+      Ok("upload") => TransferDirection::Upload,
+      _ => TransferDirection::Both,
+    };
+
+    Ok(Some(transfer))
+  }
+
+  /// Every agent with an `lfs.customtransfer.<name>.path`, read as [`CustomTransfer::from_config`] does.
+  pub fn all_from_config(config: &git2::Config) -> Result<Vec<Self>, Error> {
+    let mut names = Vec::<String>::new();
+    let mut entries = config.entries(Some(r"lfs\.customtransfer\..*\.path"))?;
+    while let Some(entry) = entries.next() {
+      let entry = entry?;
+      let name = std::str::from_utf8(entry.name_bytes())
+        .ok()
+        .and_then(|n| n.strip_prefix("lfs.customtransfer."))
+        .and_then(|n| n.strip_suffix(".path"));
+      if let Some(name) = name
+        && !names.iter().any(|n| n == name)
+      {
+        names.push(name.to_string());
+      }
+    }
+
+    let mut transfers = Vec::with_capacity(names.len());
+    for name in names {
+      transfers.extend(Self::from_config(config, &name)?);
+    }
+
+    Ok(transfers)
+  }
+
+  pub fn with_args(mut self, args: &[&str]) -> Self {
+    self.args = args.iter().map(|a| a.to_string()).collect();
+    self
+  }
+
+  pub fn with_direction(mut self, direction: TransferDirection) -> Self {
+    self.direction = direction;
+    self
+  }
+
+  /// The remote name reported to the agent on `init`.
+  pub fn with_remote(mut self, remote: &str) -> Self {
+    self.remote = remote.to_string();
+    self
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn supports(&self, operation: &str) -> bool {
+    match self.direction {
+      TransferDirection::Both => true,
+      TransferDirection::Download => operation == "download",
+      TransferDirection::Upload => operation == "upload",
+    }
+  }
+
+  /// Starts the agent on a thread of its own, which does all of the blocking reads and writes of its pipes.
+  async fn start(&self, operation: &str) -> Result<TransferAgent, RemoteError> {
+    debug!(name = %self.name, path = %self.path, operation, "custom transfer: starting agent");
+
+    let child = Command::new(&self.path)
+      .args(&self.args)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .spawn()
+      .map_err(|e| RemoteError::Agent(format!("can't start '{}': {}", self.path, e)))?;
+
+    let (requests, pending) = mpsc::channel();
+    let (replies, received) = futures::channel::mpsc::unbounded();
+    std::thread::Builder::new()
+      .name(format!("lfs-agent-{}", self.name))
+      .spawn(move || serve(child, pending, replies))
+      .map_err(|e| RemoteError::Agent(format!("can't start '{}': {}", self.path, e)))?;
+
+    let mut agent = TransferAgent { requests, replies: received };
+
+    let init = Request::Init { operation, remote: &self.remote, concurrent: false, concurrenttransfers: 1 };
+    let res = agent.exchange(&init, &mut |_| {}).await?;
+    if let Some(error) = res.error {
+      agent.terminate();
+      return Err(RemoteError::Agent(format!("init failed: {} - {}", error.code, error.message)));
+    }
+
+    Ok(agent)
+  }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Request<'a> {
+  Init { operation: &'a str, remote: &'a str, concurrent: bool, concurrenttransfers: usize },
+  Upload { oid: &'a str, size: u64, path: &'a Path, action: &'a ObjectAction },
+  Download { oid: &'a str, size: u64, action: &'a ObjectAction },
+  Terminate,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+  event: Option<String>,
+  path: Option<PathBuf>,
+  error: Option<ObjectError>,
+  bytes_so_far: Option<u64>,
+}
+
+/// A message for the agent, and whether to wait for its reply.
+type Message = (String, bool);
+
+struct TransferAgent {
+  requests: mpsc::Sender<Message>,
+  replies: UnboundedReceiver<Result<Response, RemoteError>>,
+}
+
+impl TransferAgent {
+  /// Sends one message and reads replies until one that isn't progress, which is handed to `on_progress`.
+  async fn exchange(
+    &mut self,
+    req: &Request<'_>,
+    on_progress: &mut dyn FnMut(u64),
+  ) -> Result<Response, RemoteError> {
+    let line = serde_json::to_string(req).map_err(|e| RemoteError::Agent(e.to_string()))?;
+    let exited = || RemoteError::Agent("the agent exited before replying".to_string());
+    self.requests.send((line, true)).map_err(|_| exited())?;
+
+    loop {
+      let res = self.replies.next().await.ok_or_else(exited)??;
+      match res.event.as_deref() {
+        Some("progress") => on_progress(res.bytes_so_far.unwrap_or_default()),
+        _ => return Ok(res),
+      }
+    }
+  }
+
+  async fn transfer(
+    &mut self,
+    req: &Request<'_>,
+    on_progress: &mut dyn FnMut(u64),
+  ) -> Result<Response, RemoteError> {
+    let res = self.exchange(req, on_progress).await?;
+    match res.error {
+      Some(error) => Err(RemoteError::ObjectError(format!("{} - {}", error.code, error.message))),
+      None => Ok(res),
+    }
+  }
+
+  /// Asks the agent to exit; its thread closes the pipes and waits for it.
+  fn terminate(self) {
+    if let Ok(line) = serde_json::to_string(&Request::Terminate) {
+      let _ = self.requests.send((line, false));
+    }
+  }
+}
+
+/// Runs on the agent's thread: writes each message to the agent and passes its replies back until the
+/// messages stop or the agent is told to terminate.
+fn serve(
+  mut child: Child,
+  messages: mpsc::Receiver<Message>,
+  replies: UnboundedSender<Result<Response, RemoteError>>,
+) {
+  let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+    let _ = replies.unbounded_send(Err(RemoteError::EmptyResponse));
+    return;
+  };
+  let mut stdout = BufReader::new(stdout);
+
+  for (line, wants_reply) in messages {
+    if let Err(e) = writeln!(stdin, "{}", line).and_then(|()| stdin.flush()) {
+      let _ = replies.unbounded_send(Err(e.into()));
+      break;
+    }
+
+    if !wants_reply {
+      break;
+    }
+
+    loop {
+      let res = read_response(&mut stdout);
+      let last = !matches!(&res, Ok(res) if res.event.as_deref() == Some("progress"));
+      if replies.unbounded_send(res).is_err() || last {
+        break;
+      }
+    }
+  }
+
+  drop(stdin);
+  if let Err(e) = child.wait() {
+    warn!(error = %e, "custom transfer: agent didn't exit cleanly");
+  }
+}
+
+fn read_response(stdout: &mut impl BufRead) -> Result<Response, RemoteError> {
+  let mut line = String::new();
+  if stdout.read_line(&mut line)? == 0 {
+    return Err(RemoteError::Agent("the agent exited before replying".to_string()));
+  }
+
+  serde_json::from_str::<Response>(&line)
+    .map_err(|e| RemoteError::Agent(format!("invalid message '{}': {}", line.trim(), e)))
+}
+
+impl<C: LfsRemote + Send + Sync> LfsClient<'_, C> {
+  fn report(&self, progress: Progress) {
+    if let Some(on_progress) = &self.on_progress {
+      on_progress(progress);
+    }
+  }
+
+  pub(super) fn custom_transfer_for(&self, response: &BatchResponse) -> Option<&CustomTransfer> {
+    let name = response.transfer.as_deref()?;
+    self.custom_transfers.iter().find(|t| t.name == name)
+  }
+
+  /// Downloads a batch through the agent, one object at a time, checking each against its pointer before
+  /// it's moved into the store.
+  pub(super) async fn agent_download(
+    &self,
+    transfer: &CustomTransfer,
+    response: BatchResponse,
+    pointers: &[Pointer],
+  ) -> Vec<(String, Result<(), RemoteError>)> {
+    let mut agent = match transfer.start("download").await {
+      Ok(agent) => agent,
+      Err(e) => return failed_to_start(response, e),
+    };
+
+    let total_objects = response.objects.len();
+    let total_bytes = response.objects.iter().map(|o| o.size).sum::<u64>() as usize;
+    let mut handled_bytes = 0;
+
+    let mut results = Vec::with_capacity(total_objects);
+    for (n, object) in response.objects.into_iter().enumerate() {
+      let base = handled_bytes;
+      handled_bytes += object.size as usize;
+
+      let result = async {
+        if let Some(error) = object.error.as_ref() {
+          return Err(RemoteError::ObjectError(format!("{} - {}", error.code, error.message)));
+        }
+
+        let Some(action) = object.actions.as_ref().and_then(|a| a.download.as_ref()) else {
+          return Ok(());
+        };
+
+        let pointer =
+          pointers.iter().find(|p| p.hex() == object.oid).ok_or_else(|| unexpected_object(&object.oid))?;
+        let mut on_progress = |bytes: u64| {
+          self.report(Progress::Download(ProgressEvent {
+            total_objects,
+            total_bytes,
+            bytes_handled: base + bytes as usize,
+            objects_handled: n,
+            next_object_size: object.size as usize,
+          }))
+        };
+
+        on_progress(0);
+        info!(oid = %object.oid, agent = %transfer.name, "download ({}/{}): via custom transfer", n + 1, total_objects);
+        let req = Request::Download { oid: &object.oid, size: object.size, action };
+        let path = agent.transfer(&req, &mut on_progress).await?.path.ok_or(RemoteError::EmptyResponse)?;
+
+        let content = std::fs::read(&path)?;
+        let _ = std::fs::remove_file(&path);
+        if Pointer::from_blob_bytes(&content)? != *pointer {
+          return Err(RemoteError::ChecksumMismatch);
+        }
+
+        Ok(self.store.put_bytes(pointer, &content)?)
+      }
+      .await;
+
+      results.push((object.oid, result));
+    }
+
+    agent.terminate();
+    results
+  }
+
+  /// Uploads a batch through the agent, then verifies the objects the server asks for as a basic upload
+  /// would. Like downloads, a failed object doesn't stop the rest of the batch.
+  pub(super) async fn agent_upload(
+    &self,
+    transfer: &CustomTransfer,
+    response: BatchResponse,
+    pointers: &[Pointer],
+  ) -> Vec<(String, Result<(), RemoteError>)> {
+    let mut agent = match transfer.start("upload").await {
+      Ok(agent) => agent,
+      Err(e) => return failed_to_start(response, e),
+    };
+
+    let total_objects = response.objects.len();
+    let total_bytes = response.objects.iter().map(|o| o.size).sum::<u64>() as usize;
+    let mut handled_bytes = 0;
+
+    let mut results = Vec::with_capacity(total_objects);
+    for (n, object) in response.objects.into_iter().enumerate() {
+      let base = handled_bytes;
+      handled_bytes += object.size as usize;
+
+      let result = async {
+        if let Some(error) = object.error.as_ref() {
+          return Err(RemoteError::ObjectError(format!("{} - {}", error.code, error.message)));
+        }
+
+        let Some(actions) = object.actions.as_ref() else {
+          return Ok(());
+        };
+
+        let pointer =
+          pointers.iter().find(|p| p.hex() == object.oid).ok_or_else(|| unexpected_object(&object.oid))?;
+        let event = |bytes: u64| ProgressEvent {
+          total_objects,
+          total_bytes,
+          bytes_handled: base + bytes as usize,
+          objects_handled: n,
+          next_object_size: object.size as usize,
+        };
+
+        if let Some(action) = actions.upload.as_ref() {
+          let file = self.upload_file(pointer)?;
+          info!(oid = %object.oid, agent = %transfer.name, "upload ({}/{}): via custom transfer", n + 1, total_objects);
+          let req = Request::Upload { oid: &object.oid, size: object.size, path: file.path(), action };
+          agent.transfer(&req, &mut |bytes| self.report(Progress::Upload(event(bytes)))).await?;
+        }
+
+        if let Some(action) = actions.verify.as_ref() {
+          self.report(Progress::Verify(event(object.size)));
+          self.client.verify(action, pointer).await?;
+        }
+
+        Ok(())
+      }
+      .await;
+
+      results.push((object.oid, result));
+    }
+
+    agent.terminate();
+    results
+  }
+
+  /// The file the agent uploads an object from: the object itself when the store keeps it as a file,
+  /// otherwise a private copy in the lfs temp directory that's removed once the upload is done.
+  fn upload_file(&self, pointer: &Pointer) -> Result<UploadFile, RemoteError> {
+    // Objects only found in an alternate are copied into the store when they're opened.
+    let mut object = self.store.open(pointer)?;
+    if let Some(object_dir) = self.store.object_dir() {
+      return Ok(UploadFile::Object(object_dir.join(pointer.path())));
+    }
+
+    std::fs::create_dir_all(&self.tmp_dir)?;
+    let path = self.tmp_dir.join(format!("{}-{}", pointer.hex(), std::process::id()));
+
+    let mut options = std::fs::File::options();
+    options.create_new(true).write(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(&path)?;
+    let copy = UploadFile::Copy(path);
+    std::io::copy(&mut object, &mut file)?;
+    Ok(copy)
+  }
+}
+
+fn failed_to_start(response: BatchResponse, e: RemoteError) -> Vec<(String, Result<(), RemoteError>)> {
+  let message = e.to_string();
+  response.objects.into_iter().map(|o| (o.oid, Err(RemoteError::Agent(message.clone())))).collect()
+}
+
+enum UploadFile {
+  Object(PathBuf),
+  Copy(PathBuf),
+}
+
+impl UploadFile {
+  fn path(&self) -> &Path {
+    match self {
+      UploadFile::Object(path) | UploadFile::Copy(path) => path,
+    }
+  }
+}
+
+impl Drop for UploadFile {
+  fn drop(&mut self) {
+    if let UploadFile::Copy(path) = self {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+}
@@ -3,74 +3,151 @@ use serde::Serialize;
 
 use std::collections::HashMap;
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct BatchRequest {
   pub operation: String,
+  #[serde(default)]
   pub transfers: Vec<String>,
   pub objects: Vec<BatchObject>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub hash_algo: Option<String>,
+  #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+  pub ref_name: Option<Ref>,
+}
+
+/// The ref a request is made for, which servers may use to authorize it per branch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Ref {
+  pub name: String,
 }
 
-#[derive(Serialize, Debug)]
+impl Ref {
+  pub fn new(name: &str) -> Self {
+    Self { name: name.to_string() }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct BatchObject {
   pub oid: String,
   pub size: u64,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct BatchResponse {
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub transfer: Option<String>,
   pub objects: Vec<BatchResponseObject>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub hash_algo: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+impl BatchResponse {
+  /// Copies each object's `authenticated` onto its actions, which are later sent without the object.
+  pub fn mark_authenticated(&mut self) {
+    for object in self.objects.iter_mut().filter(|o| o.authenticated == Some(true)) {
+      let Some(ObjectActions { download, upload, verify, parts, commit, abort }) = object.actions.as_mut()
+      else {
+        continue;
+      };
+
+      let parts = parts.iter_mut().flatten().map(|p| &mut p.action);
+      let multipart = [commit, abort].into_iter().flatten().map(|m| &mut m.action);
+      for action in [download, upload, verify].into_iter().flatten().chain(parts).chain(multipart) {
+        action.authenticated = true;
+      }
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct BatchResponseObject {
   pub oid: String,
   pub size: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub authenticated: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub actions: Option<ObjectActions>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub error: Option<ObjectError>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct ObjectActions {
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub download: Option<ObjectAction>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub upload: Option<ObjectAction>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub verify: Option<ObjectAction>,
+  /// `multipart-basic` uploads: the parts to `PUT`, then the request that joins them or gives up on them.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub parts: Option<Vec<PartAction>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub commit: Option<MultipartAction>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub abort: Option<MultipartAction>,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ObjectAction {
   pub href: String,
   #[serde(default)]
   pub header: HashMap<String, String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub expires_in: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub expires_at: Option<String>,
+  /// Whether the href works without the client's own credentials; see [`BatchResponse::mark_authenticated`].
+  #[serde(skip)]
+  pub authenticated: bool,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PartAction {
+  #[serde(flatten)]
+  pub action: ObjectAction,
+  #[serde(default)]
+  pub pos: u64,
+  /// Missing on the last part, which runs to the end of the object.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub size: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub want_digest: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MultipartAction {
+  #[serde(flatten)]
+  pub action: ObjectAction,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub method: Option<String>,
+  /// Sent as is when present; otherwise the request carries the object's oid and size.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct ObjectError {
   pub code: u32,
   pub message: String,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LockRequest {
   pub path: String,
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub ref_name: Option<String>,
+  #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+  pub ref_name: Option<Ref>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LockResponse {
   pub lock: Lock,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub message: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Lock {
   pub id: String,
   pub path: String,
@@ -78,41 +155,93 @@ pub struct Lock {
   pub owner: LockOwner,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct LockOwner {
   pub name: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct LockListRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub path: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cursor: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub limit: Option<u32>,
+  /// Sent as a query parameter, so unlike the other requests it's the bare ref name.
+  #[serde(rename = "refspec", skip_serializing_if = "Option::is_none")]
+  pub ref_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct LockListResponse {
   pub locks: Vec<Lock>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub next_cursor: Option<String>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct UnlockRequest {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub force: Option<bool>,
-  #[serde(skip_serializing_if = "Option::is_none")]
-  pub ref_name: Option<String>,
+  #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+  pub ref_name: Option<Ref>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct UnlockResponse {
   pub lock: Lock,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub message: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct VerifyLocksRequest {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cursor: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub limit: Option<u32>,
+  #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+  pub ref_name: Option<Ref>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
 pub struct VerifyLocksResponse {
   pub ours: Vec<Lock>,
   pub theirs: Vec<Lock>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub next_cursor: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ErrorResponse {
   pub message: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub documentation_url: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
   pub request_id: Option<String>,
 }
+
+impl ErrorResponse {
+  /// An error that didn't come from a server, so has no documentation link or request id.
+  pub fn new(message: impl Into<String>) -> Self {
+    Self { message: message.into(), documentation_url: None, request_id: None }
+  }
+}
+
+impl std::fmt::Display for ErrorResponse {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.message)?;
+    if let Some(url) = &self.documentation_url {
+      write!(f, " (see {})", url)?;
+    }
+
+    if let Some(id) = &self.request_id {
+      write!(f, " [request id: {}]", id)?;
+    }
+
+    Ok(())
+  }
+}
@@ -1,10 +1,24 @@
-use std::fs::File;
-use std::io::BufWriter;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::time::Duration;
 
 use crate::Pointer;
+use crate::ext::RepoLfsExt;
+use crate::fetch::FetchObject;
+use crate::fetch::FetchPlan;
+use crate::fetch::FetchResult;
+use crate::fetch::FetchStatus;
+use crate::hooks::PrePush;
+use crate::migrate::MigrateOptions;
+use crate::migrate::MigrateReport;
+use crate::prune::PrunePlan;
+use crate::prune::PruneReport;
+use crate::push::PushUpdate;
+use crate::remote::custom::CustomTransfer;
+use crate::store::ObjectStore;
 
 use async_trait::async_trait;
 
@@ -17,6 +31,11 @@ pub use dto::*;
 
 mod dto;
 
+pub mod custom;
+
+mod multipart;
+mod tus;
+
 #[cfg(all(feature = "reqwest-backend", not(target_family = "wasm")))]
 pub mod reqwest;
 
@@ -24,17 +43,29 @@ pub const MEDIA_TYPE: &str = "application/vnd.git-lfs+json";
 
 #[derive(thiserror::Error, Debug)]
 pub enum RemoteError {
-  #[error("access denied")]
-  AccessDenied,
+  #[error("access denied: {0}")]
+  AccessDenied(ErrorResponse),
+
+  #[error("rejected by the server: {0}")]
+  Validation(ErrorResponse),
+
+  #[error("rate limited: {error}")]
+  RateLimited { error: ErrorResponse, reset_at: Option<std::time::SystemTime> },
+
+  #[error("the server is out of storage: {0}")]
+  InsufficientStorage(ErrorResponse),
+
+  #[error("not implemented by the server: {0}")]
+  NotImplemented(ErrorResponse),
 
   #[error("object error: {0}")]
   ObjectError(String),
 
-  #[error("not found")]
-  NotFound,
+  #[error("not found: {0}")]
+  NotFound(ErrorResponse),
 
-  #[error("batch failed: {0}")]
-  Batch(String),
+  #[error("http {status}: {error}")]
+  Http { status: u16, error: ErrorResponse },
 
   #[error("download failed: {0}")]
   Download(String),
@@ -42,8 +73,8 @@ pub enum RemoteError {
   #[error("upload failed: {0}")]
   Upload(String),
 
-  #[error("verify failed: {0}")]
-  Verify(String),
+  #[error("transfer agent: {0}")]
+  Agent(String),
 
   #[error("checksum mismatch")]
   ChecksumMismatch,
@@ -57,10 +88,23 @@ pub enum RemoteError {
   #[error("io: {0}")]
   Io(#[from] std::io::Error),
 
+  #[error("object store: {0}")]
+  Store(Box<crate::Error>),
+
   #[error(transparent)]
   Custom(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
 
+impl From<crate::Error> for RemoteError {
+  fn from(err: crate::Error) -> Self {
+    match err {
+      crate::Error::Remote(err) => err,
+      crate::Error::Io(err) => RemoteError::Io(err),
+      err => RemoteError::Store(Box::new(err)),
+    }
+  }
+}
+
 pub type Write = dyn std::io::Write + Send;
 pub type Read = dyn std::io::Read + Send;
 
@@ -124,18 +168,88 @@ pub trait LfsRemote: Send + Sync {
   async fn download(&self, action: &ObjectAction, to: &mut Write) -> Result<Pointer, RemoteError>;
   async fn upload(&self, action: &ObjectAction, blob: &[u8]) -> Result<(), RemoteError>;
   async fn verify(&self, action: &ObjectAction, pointer: &Pointer) -> Result<(), RemoteError>;
+
+  /// Remotes without the locking api report every lock request as not implemented.
+  async fn lock(&self, _req: LockRequest) -> Result<LockResponse, RemoteError> {
+    Err(not_implemented("locking"))
+  }
+
+  async fn locks(&self, _req: &LockListRequest) -> Result<LockListResponse, RemoteError> {
+    Err(not_implemented("locking"))
+  }
+
+  async fn unlock(&self, _id: &str, _req: UnlockRequest) -> Result<UnlockResponse, RemoteError> {
+    Err(not_implemented("locking"))
+  }
+
+  /// Whether `multipart-basic` can be offered to the server; parts go through [`LfsRemote::upload`], the
+  /// commit and abort requests through [`LfsRemote::multipart`].
+  fn supports_multipart(&self) -> bool {
+    false
+  }
+
+  async fn multipart(&self, _action: &MultipartAction, _pointer: &Pointer) -> Result<(), RemoteError> {
+    Err(not_implemented("multipart-basic"))
+  }
+
+  /// Whether `tus` can be offered to the server; a resumable upload asks [`LfsRemote::upload_offset`] where
+  /// to continue and sends the rest with [`LfsRemote::upload_at`].
+  fn supports_tus(&self) -> bool {
+    false
+  }
+
+  async fn upload_offset(&self, _action: &ObjectAction) -> Result<u64, RemoteError> {
+    Err(not_implemented("tus"))
+  }
+
+  async fn upload_at(&self, _action: &ObjectAction, _offset: u64, _blob: Vec<u8>) -> Result<(), RemoteError> {
+    Err(not_implemented("tus"))
+  }
+}
+
+/// A batch response object that doesn't match any of the requested ones.
+pub(crate) fn unexpected_object(oid: &str) -> RemoteError {
+  RemoteError::NotFound(ErrorResponse::new(format!("the server answered for unrequested object {}", oid)))
+}
+
+fn not_implemented(what: &str) -> RemoteError {
+  RemoteError::NotImplemented(ErrorResponse::new(format!("{} isn't supported by this remote", what)))
 }
 
 pub struct LfsClient<'a, C: Send + Sync> {
-  repo: &'a git2::Repository,
   client: C,
+  store: Arc<dyn ObjectStore>,
   on_progress: Option<Box<OnProgress<'a>>>,
   concurrency_limit: usize,
+  part_concurrency_limit: usize,
+  custom_transfers: Vec<CustomTransfer>,
+  ref_name: Option<String>,
+  tmp_dir: PathBuf,
 }
 
 impl<'a, C: LfsRemote + Send + Sync> LfsClient<'a, C> {
-  pub fn new(repo: &'a git2::Repository, client: C) -> Self {
-    Self { repo, client, on_progress: None, concurrency_limit: 1 }
+  /// A client for `repo`'s lfs storage, the same one its filter uses; fails when that can't be resolved.
+  pub fn new(repo: &'a git2::Repository, client: C) -> Result<Self, crate::Error> {
+    let store = repo.lfs_object_store()?;
+    let ref_name = repo
+      .head()
+      .ok()
+      .filter(|head| head.is_branch())
+      .and_then(|head| std::str::from_utf8(head.name_bytes()).ok().map(str::to_string));
+    Ok(Self {
+      client,
+      store,
+      on_progress: None,
+      concurrency_limit: 1,
+      part_concurrency_limit: 4,
+      custom_transfers: Vec::new(),
+      ref_name,
+      tmp_dir: crate::store::storage_dir(repo)?.join("tmp"),
+    })
+  }
+
+  pub fn object_store(self, store: Arc<dyn ObjectStore>) -> Self {
+    Self { store, ..self }
   }
 
   pub fn concurrency_limit(self, concurrency_limit: usize) -> Self {
@@ -146,58 +260,300 @@ impl<'a, C: LfsRemote + Send + Sync> LfsClient<'a, C> {
     Self { on_progress, ..self }
   }
 
+  /// How many parts of one `multipart-basic` upload are sent at once.
+  pub fn part_concurrency_limit(self, part_concurrency_limit: usize) -> Self {
+    Self { part_concurrency_limit: part_concurrency_limit.max(1), ..self }
+  }
+
+  /// The ref requests are made for, the checked out branch by default. Pushes of ref updates send the
+  /// remote ref they update instead.
+  pub fn ref_name(self, ref_name: Option<&str>) -> Self {
+    Self { ref_name: ref_name.map(str::to_string), ..self }
+  }
+
+  /// Offers an agent to the server ahead of `basic`; the agent moves the objects if the server picks it.
+  pub fn custom_transfer(mut self, transfer: CustomTransfer) -> Self {
+    self.custom_transfers.push(transfer);
+    self
+  }
+
+  fn request_ref(&self) -> Option<Ref> {
+    self.ref_name.as_deref().map(Ref::new)
+  }
+
+  /// Sends a batch request, carrying each object's `authenticated` over to its actions whatever the
+  /// remote did with it.
+  async fn batch(&self, request: BatchRequest) -> Result<BatchResponse, RemoteError> {
+    let mut response = self.client.batch(request).await?;
+    response.mark_authenticated();
+    Ok(response)
+  }
+
+  /// Transfer adapters to negotiate for `operation`, most preferred first.
+  fn transfers(&self, operation: &str) -> Vec<String> {
+    let custom = self.custom_transfers.iter().filter(|t| t.supports(operation)).map(|t| t.name().to_string());
+    let upload = operation == "upload";
+    let multipart = (upload && self.client.supports_multipart()).then(|| "multipart-basic".to_string());
+    let tus = (upload && self.client.supports_tus()).then(|| "tus".to_string());
+    custom.chain(multipart).chain(tus).chain(std::iter::once("basic".to_string())).collect()
+  }
+
   pub async fn pull(&self, pointers: &[Pointer]) -> Result<(), RemoteError> {
+    let mut missing = Vec::with_capacity(pointers.len());
+    for pointer in pointers {
+      if self.store.contains(pointer)? {
+        debug!(pointer = %pointer, "pull: object is already available locally, skipping");
+        continue;
+      }
+
+      missing.push(*pointer);
+    }
+
+    let pointers = missing.as_slice();
+
     if pointers.is_empty() {
       return Ok(());
     }
 
     let request = BatchRequest {
       operation: "download".to_string(),
-      transfers: vec!["basic".to_string()],
+      transfers: self.transfers("download"),
       objects: pointers.iter().map(|p| BatchObject { oid: p.hex(), size: p.size() as u64 }).collect(),
       hash_algo: Some("sha256".to_string()),
+      ref_name: self.request_ref(),
     };
 
-    let response = self.client.batch(request).await?;
+    let response = self.batch(request).await?;
 
     self.download_objects(response, pointers).await
   }
 
+  /// Downloads the plan's objects that aren't available locally, reporting the outcome for each of them
+  /// instead of stopping at the first failure. Objects are requested for the ref they were found under,
+  /// one batch per ref.
+  pub async fn fetch(&self, plan: &FetchPlan) -> Result<Vec<FetchResult>, RemoteError> {
+    let mut results = Vec::with_capacity(plan.objects.len());
+    let mut refs = Vec::<(Option<&str>, Vec<&FetchObject>)>::new();
+
+    for object in plan.objects.iter() {
+      if self.store.contains(&object.pointer)? {
+        results.push(FetchResult {
+          pointer: object.pointer,
+          path: object.path.clone(),
+          status: FetchStatus::Present,
+        });
+      } else {
+        let ref_name = object.ref_name.as_deref();
+        match refs.iter_mut().find(|(r, _)| *r == ref_name) {
+          Some((_, objects)) => objects.push(object),
+          None => refs.push((ref_name, vec![object])),
+        }
+      }
+    }
+
+    for (ref_name, objects) in refs {
+      let missing = objects.iter().map(|o| o.pointer).collect::<Vec<_>>();
+      let request = BatchRequest {
+        operation: "download".to_string(),
+        transfers: self.transfers("download"),
+        objects: missing.iter().map(|p| BatchObject { oid: p.hex(), size: p.size() as u64 }).collect(),
+        hash_algo: Some("sha256".to_string()),
+        ref_name: ref_name.map(Ref::new).or_else(|| self.request_ref()),
+      };
+
+      let response = self.batch(request).await?;
+      let mut outcomes = self.download_each(response, &missing).await;
+
+      for object in objects {
+        let status = match outcomes.iter().position(|(oid, _)| *oid == object.pointer.hex()) {
+          Some(i) => match outcomes.swap_remove(i).1 {
+            Err(e) => FetchStatus::Failed(e),
+            Ok(()) if self.store.contains(&object.pointer)? => FetchStatus::Downloaded,
+            Ok(()) => FetchStatus::Failed(RemoteError::EmptyResponse),
+          },
+          None => FetchStatus::Failed(RemoteError::NotFound(ErrorResponse::new("no answer for the object"))),
+        };
+
+        results.push(FetchResult { pointer: object.pointer, path: object.path.clone(), status });
+      }
+    }
+
+    Ok(results)
+  }
+
+  /// Downloads the objects `migrate export` needs, then rewrites history with them inlined.
+  pub async fn migrate_export(
+    &self,
+    repo: &git2::Repository,
+    options: &MigrateOptions,
+  ) -> Result<MigrateReport, RemoteError> {
+    let missing = crate::migrate::export_missing(repo, self.store.as_ref(), options)?;
+    if !missing.is_empty() {
+      info!(missing = missing.len(), "migrate export: downloading missing lfs objects");
+      self.pull(&missing).await?;
+    }
+
+    Ok(crate::migrate::export(repo, self.store.as_ref(), options)?)
+  }
+
   pub async fn push(&self, pointers: &[Pointer]) -> Result<(), RemoteError> {
+    self.push_for(pointers, self.ref_name.as_deref()).await
+  }
+
+  async fn push_for(&self, pointers: &[Pointer], ref_name: Option<&str>) -> Result<(), RemoteError> {
     if pointers.is_empty() {
       return Ok(());
     }
 
     let request = BatchRequest {
       operation: "upload".to_string(),
-      transfers: vec!["basic".to_string()],
+      transfers: self.transfers("upload"),
       objects: pointers.iter().map(|p| BatchObject { oid: p.hex(), size: p.size() as u64 }).collect(),
       hash_algo: Some("sha256".to_string()),
+      ref_name: ref_name.map(Ref::new),
     };
 
-    let response = self.client.batch(request).await?;
+    let mut response = self.batch(request).await?;
+
+    // Objects the server already has come back without actions; leave them out of the progress totals.
+    let requested = response.objects.len();
+    response.objects.retain(|o| {
+      o.error.is_some() || o.actions.as_ref().is_some_and(|a| a.upload.is_some() || a.parts.is_some())
+    });
+    if response.objects.len() < requested {
+      info!("upload: remote already has {} of {} objects", requested - response.objects.len(), requested);
+    }
 
     self.upload_objects(response, pointers).await
   }
 
+  /// Uploads the objects a push of `updates` introduces, as a pre-push hook would.
+  pub async fn push_updates(
+    &self,
+    repo: &git2::Repository,
+    updates: &[PushUpdate],
+  ) -> Result<(), RemoteError> {
+    // Each ref's objects are pushed for that ref, so servers can authorize them per branch.
+    for update in updates.iter().filter(|u| !u.is_delete()) {
+      let pointers = crate::push::objects_to_push(repo, std::slice::from_ref(update))?;
+      debug!(objects = pointers.len(), remote_ref = %update.remote_ref, "push: collected objects for ref update");
+      self.push_for(&pointers, Some(&update.remote_ref)).await?;
+    }
+
+    Ok(())
+  }
+
+  /// Uploads what a pre-push hook's updates introduce; deletions push nothing and new branches only push
+  /// what no remote-tracking ref already has.
+  pub async fn pre_push(&self, repo: &git2::Repository, pre_push: &PrePush) -> Result<(), RemoteError> {
+    info!(remote = %pre_push.remote, url = %pre_push.url, updates = pre_push.updates.len(), "pre-push: uploading lfs objects");
+    self.push_updates(repo, &pre_push.updates).await
+  }
+
+  pub async fn lock(&self, path: &str) -> Result<Lock, RemoteError> {
+    let res = self.client.lock(LockRequest { path: path.to_string(), ref_name: self.request_ref() }).await?;
+    info!(path, id = %res.lock.id, "lock: created");
+    Ok(res.lock)
+  }
+
+  /// Releases a lock by id; `force` breaks a lock someone else owns.
+  pub async fn unlock(&self, id: &str, force: bool) -> Result<Lock, RemoteError> {
+    let res = self
+      .client
+      .unlock(id, UnlockRequest { force: force.then_some(true), ref_name: self.request_ref() })
+      .await?;
+    info!(id, path = %res.lock.path, "unlock: released");
+    Ok(res.lock)
+  }
+
+  /// Lists locks, optionally only the one on `path`, following the remote's pagination.
+  pub async fn locks(&self, path: Option<&str>) -> Result<Vec<Lock>, RemoteError> {
+    let mut req = LockListRequest {
+      path: path.map(str::to_string),
+      ref_name: self.ref_name.clone(),
+      ..Default::default()
+    };
+    let mut locks = Vec::new();
+
+    loop {
+      let res = self.client.locks(&req).await?;
+      locks.extend(res.locks);
+
+      match res.next_cursor {
+        Some(cursor) if !cursor.is_empty() => req.cursor = Some(cursor),
+        _ => return Ok(locks),
+      }
+    }
+  }
+
+  /// Deletes the plan's candidates, but only those the remote confirms it can serve back.
+  pub async fn prune(&self, plan: &PrunePlan) -> Result<PruneReport, RemoteError> {
+    if plan.candidates.is_empty() {
+      return Ok(PruneReport { dry_run: plan.dry_run, ..Default::default() });
+    }
+
+    let request = BatchRequest {
+      operation: "download".to_string(),
+      transfers: self.transfers("download"),
+      objects: plan.candidates.iter().map(|p| BatchObject { oid: p.hex(), size: p.size() as u64 }).collect(),
+      hash_algo: Some("sha256".to_string()),
+      ref_name: self.request_ref(),
+    };
+
+    let response = self.batch(request).await?;
+    debug!(response = ?response, "prune: got batch response");
+
+    let verified = response
+      .objects
+      .iter()
+      .filter(|o| o.error.is_none() && o.actions.as_ref().is_some_and(|a| a.download.is_some()))
+      .filter_map(|o| plan.candidates.iter().find(|p| p.hex() == o.oid && p.size() as u64 == o.size))
+      .copied()
+      .collect::<HashSet<_>>();
+
+    Ok(plan.apply(self.store.as_ref(), &verified)?)
+  }
+
   async fn download_objects(&self, response: BatchResponse, pointers: &[Pointer]) -> Result<(), RemoteError> {
-    let object_dir = self.repo.path().join("lfs/objects");
+    let r = self.download_each(response, pointers).await;
+    for r in r.iter().filter_map(|(_, r)| r.as_ref().err()) {
+      error!(error = %r, "download failed");
+    }
+
+    if let Some(res) = r.into_iter().find_map(|(_, r)| r.err()) {
+      return Err(res);
+    }
 
+    Ok(())
+  }
+
+  async fn download_each(
+    &self,
+    response: BatchResponse,
+    pointers: &[Pointer],
+  ) -> Vec<(String, Result<(), RemoteError>)> {
     debug!(response = ?response, "download: got batch response");
+    if let Some(transfer) = self.custom_transfer_for(&response) {
+      return self.agent_download(transfer, response, pointers).await;
+    }
+
     let total_objects = response.objects.len();
     let total_bytes = response.objects.iter().map(|o| o.size).sum::<u64>() as usize;
 
     let handled_bytes = AtomicUsize::new(0);
     let handled_objects = AtomicUsize::new(0);
 
-    let futures = response.objects.into_iter().map(async |object| {
+    let download = async |object: BatchResponseObject| {
       let n = handled_objects.fetch_add(1, Ordering::Relaxed) + 1;
       if let Some(error) = object.error {
         return Err(RemoteError::ObjectError(format!("{} - {}", error.code, error.message)));
       }
 
       let Some(actions) = object.actions else {
-        debug!( "download ({}/{}): server didn't want us to do anything with '{}' (actions is None); skip", n, total_objects, object.oid);
+        debug!(
+          "download ({}/{}): server didn't want us to do anything with '{}' (actions is None); skip",
+          n, total_objects, object.oid
+        );
         return Ok(());
       };
 
@@ -215,41 +571,37 @@ impl<'a, C: LfsRemote + Send + Sync> LfsClient<'a, C> {
         on_progress(Progress::Download(event));
       }
 
-      let pointer = pointers.iter().find(|p| p.hex() == object.oid).ok_or(RemoteError::NotFound)?;
+      let pointer =
+        pointers.iter().find(|p| p.hex() == object.oid).ok_or_else(|| unexpected_object(&object.oid))?;
 
-      let path = object_dir.join(pointer.path());
-      std::fs::create_dir_all(path.parent().unwrap())?;
+      let local_path = pointer.path();
 
       let mut attempt = 0;
       let retry_delay = Duration::from_millis(500);
 
       while attempt < 3 {
-        if path.exists() {
-          std::fs::remove_file(&path)?;
-        }
-
-        let mut buf = BufWriter::new(File::options().create_new(true).write(true).open(&path)?);
+        let mut writer = self.store.put_stream(pointer)?;
 
-        let local_path = path.strip_prefix(&object_dir).unwrap_or(&path);
         info!(url = %download_action.href, size = %pointer.size(), "download ({}/{})", n, total_objects);
-        let download_result = self.client.download(&download_action, &mut buf).await;
-        drop(buf);
+        let download_result = self.client.download(&download_action, &mut writer).await;
 
         let download_checksum_result = download_result.and_then(|p| {
           if p.hash() != pointer.hash() {
             error!(path = %local_path.display(), expected = %pointer, got = %p, "download ({}/{}): checksum mismatch", n, total_objects);
-            std::fs::remove_file(&path)?;
             Err(RemoteError::ChecksumMismatch)
           } else {
-            Ok(p)
+            Ok(writer.finish()?)
           }
         });
 
         if let Err(e) = download_checksum_result {
-          error!(error = %e, "download ({}/{}): failed, retrying", n, total_objects);
           attempt += 1;
-          std::fs::remove_file(&path)?;
-          std::thread::sleep(retry_delay);
+          if attempt == 3 {
+            return Err(e);
+          }
+
+          error!(error = %e, "download ({}/{}): failed, retrying", n, total_objects);
+          futures_timer::Delay::new(retry_delay).await;
           continue;
         }
 
@@ -257,26 +609,26 @@ impl<'a, C: LfsRemote + Send + Sync> LfsClient<'a, C> {
       }
 
       Ok(())
-    });
-
-    let r = futures::stream::iter(futures).buffer_unordered(self.concurrency_limit).collect::<Vec<_>>().await;
-    for r in r.iter().filter_map(|r| r.as_ref().err()) {
-      error!(error = %r, "download failed");
-    }
+    };
 
-    if let Some(res) = r.into_iter().find_map(|r| r.err()) {
-      return Err(res);
-    }
+    let futures = response.objects.into_iter().map(|object| {
+      let oid = object.oid.clone();
+      let download = &download;
+      async move { (oid, download(object).await) }
+    });
 
-    Ok(())
+    futures::stream::iter(futures).buffer_unordered(self.concurrency_limit).collect::<Vec<_>>().await
   }
 
   async fn upload_objects(&self, response: BatchResponse, pointers: &[Pointer]) -> Result<(), RemoteError> {
-    let object_dir = self.repo.path().join("lfs/objects");
-
     debug!(response = ?response, "upload: got batch response");
+    if let Some(transfer) = self.custom_transfer_for(&response) {
+      let r = self.agent_upload(transfer, response, pointers).await;
+      return first_upload_error(r.into_iter().map(|(_, r)| r).collect());
+    }
 
     let retry_delay = Duration::from_millis(500);
+    let resumable = response.transfer.as_deref() == Some("tus");
 
     let total_objects = response.objects.len();
     let total_bytes = response.objects.iter().map(|o| o.size).sum::<u64>() as usize;
@@ -308,25 +660,37 @@ impl<'a, C: LfsRemote + Send + Sync> LfsClient<'a, C> {
         on_progress(Progress::Upload(event));
       }
 
-      let pointer = pointers.iter().find(|p| p.hex() == object.oid).ok_or(RemoteError::NotFound)?;
+      let pointer =
+        pointers.iter().find(|p| p.hex() == object.oid).ok_or_else(|| unexpected_object(&object.oid))?;
       let rel_object_path = pointer.path();
 
-      if let Some(upload_action) = actions.upload.as_ref() {
-        let object_path = object_dir.join(&rel_object_path);
-        let content = std::fs::read(object_path)?;
+      if let Some(parts) = actions.parts.as_ref() {
+        info!(oid = %object.oid, parts = parts.len(), "upload ({}/{}): multipart", n, total_objects);
+        self.upload_parts(pointer, parts, actions).await?;
+      } else if let Some(upload_action) = actions.upload.as_ref()
+        && resumable
+      {
+        info!(oid = %object.oid, "upload ({}/{}): resumable", n, total_objects);
+        self.upload_resumable(pointer, upload_action).await?;
+      } else if let Some(upload_action) = actions.upload.as_ref() {
+        let content = self.store.read_to_vec(pointer)?;
 
         let mut attempt = 0;
 
-        while attempt < 3 {
+        loop {
           debug!(url = %upload_action.href, size = %content.len(), attempt = %attempt, "upload ({}/{})", n, total_objects);
-          match self.client.upload(&upload_action, &content).await {
+          match self.client.upload(upload_action, &content).await {
             Ok(()) => break,
             Err(e) => {
-              error!( error = %e, "upload ({}/{}): failed, retrying", n, total_objects);
               attempt += 1;
+              if attempt == 3 {
+                return Err(e);
+              }
+
+              error!(error = %e, "upload ({}/{}): failed, retrying", n, total_objects);
+              futures_timer::Delay::new(retry_delay).await;
             }
           }
-          std::thread::sleep(retry_delay);
         }
       }
 
@@ -352,15 +716,18 @@ impl<'a, C: LfsRemote + Send + Sync> LfsClient<'a, C> {
     });
 
     let r = futures::stream::iter(futures).buffer_unordered(self.concurrency_limit).collect::<Vec<_>>().await;
+    first_upload_error(r)
+  }
+}
 
-    for r in r.iter().filter_map(|r| r.as_ref().err()) {
-      error!(error = %r, "upload failed");
-    }
-
-    if let Some(res) = r.into_iter().find_map(|r| r.err()) {
-      return Err(res);
-    }
+fn first_upload_error(r: Vec<Result<(), RemoteError>>) -> Result<(), RemoteError> {
+  for r in r.iter().filter_map(|r| r.as_ref().err()) {
+    error!(error = %r, "upload failed");
+  }
 
-    Ok(())
+  if let Some(res) = r.into_iter().find_map(|r| r.err()) {
+    return Err(res);
   }
+
+  Ok(())
 }
@@ -0,0 +1,141 @@
+use std::io::Read as _;
+use std::time::Duration;
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
+use futures::StreamExt;
+use md5::Md5;
+use sha2::Digest as _;
+use sha2::Sha256;
+use tracing::*;
+
+use crate::Pointer;
+use crate::remote::LfsClient;
+use crate::remote::LfsRemote;
+use crate::remote::ObjectActions;
+use crate::remote::PartAction;
+use crate::remote::Read;
+use crate::remote::RemoteError;
+
+const ATTEMPTS: usize = 3;
+
+impl<C: LfsRemote + Send + Sync> LfsClient<'_, C> {
+  /// Uploads an object as `multipart-basic` parts, several at once and each retried on its own, then asks
+  /// the server to join them. The upload is aborted if a part or the commit fails.
+  pub(super) async fn upload_parts(
+    &self,
+    pointer: &Pointer,
+    parts: &[PartAction],
+    actions: &ObjectActions,
+  ) -> Result<(), RemoteError> {
+    let mut parts = parts.iter().collect::<Vec<_>>();
+    parts.sort_by_key(|p| p.pos);
+
+    // Parts are read from the object in order as they're sent, so at most `part_concurrency_limit` of them
+    // are held in memory.
+    let mut reader = self.store.open(pointer)?;
+    let mut offset = 0;
+    let total = parts.len();
+
+    let uploads = parts.into_iter().enumerate().map(|(n, part)| {
+      let content = read_part(&mut *reader, &mut offset, part);
+      async move { self.upload_part(n + 1, total, part, content?).await }
+    });
+
+    let results =
+      futures::stream::iter(uploads).buffer_unordered(self.part_concurrency_limit).collect::<Vec<_>>().await;
+    let result = match results.into_iter().find_map(|r| r.err()) {
+      Some(e) => Err(e),
+      None => match actions.commit.as_ref() {
+        Some(commit) => self.client.multipart(commit, pointer).await,
+        None => Ok(()),
+      },
+    };
+
+    if let Err(e) = result {
+      if let Some(abort) = actions.abort.as_ref()
+        && let Err(abort_err) = self.client.multipart(abort, pointer).await
+      {
+        warn!(oid = %pointer.hex(), error = %abort_err, "multipart: abort failed");
+      }
+
+      return Err(e);
+    }
+
+    Ok(())
+  }
+
+  async fn upload_part(
+    &self,
+    n: usize,
+    total: usize,
+    part: &PartAction,
+    content: Vec<u8>,
+  ) -> Result<(), RemoteError> {
+    let mut action = part.action.clone();
+    if let Some(want_digest) = part.want_digest.as_deref() {
+      let (name, value) = digest_header(want_digest, &content)?;
+      action.header.insert(name.to_string(), value);
+    }
+
+    let mut attempt = 1;
+    loop {
+      debug!(url = %action.href, pos = part.pos, size = content.len(), attempt, "multipart: part {}/{}", n, total);
+      match self.client.upload(&action, &content).await {
+        Ok(()) => return Ok(()),
+        Err(e) if attempt < ATTEMPTS => {
+          warn!(error = %e, "multipart: part {}/{} failed, retrying", n, total);
+          attempt += 1;
+          futures_timer::Delay::new(Duration::from_millis(500)).await;
+        }
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
+
+/// The header carrying the digest a part's `want_digest` asks for: `Content-MD5` for `contentMD5`, otherwise
+/// a `Digest` header with the most preferred supported algorithm of an RFC 3230 `Want-Digest` value.
+fn digest_header(want_digest: &str, content: &[u8]) -> Result<(&'static str, String), RemoteError> {
+  if want_digest.eq_ignore_ascii_case("contentMD5") {
+    return Ok(("Content-MD5", STANDARD.encode(Md5::digest(content))));
+  }
+
+  let mut preferred: Option<(&str, f32)> = None;
+  for wanted in want_digest.split(',') {
+    let mut params = wanted.split(';');
+    let algorithm = params.next().unwrap_or_default().trim();
+    let q = params.find_map(|p| p.trim().strip_prefix("q=")).and_then(|q| q.parse().ok()).unwrap_or(1.0);
+
+    let supported = ["sha-256", "md5"].iter().any(|a| a.eq_ignore_ascii_case(algorithm));
+    if supported && q > 0.0 && preferred.is_none_or(|(_, best)| q > best) {
+      preferred = Some((algorithm, q));
+    }
+  }
+
+  match preferred {
+    Some((algorithm, _)) if algorithm.eq_ignore_ascii_case("sha-256") => {
+      Ok(("Digest", format!("SHA-256={}", STANDARD.encode(Sha256::digest(content)))))
+    }
+    Some(_) => Ok(("Digest", format!("MD5={}", STANDARD.encode(Md5::digest(content))))),
+    None => Err(RemoteError::Upload(format!("multipart: no supported digest in '{}'", want_digest))),
+  }
+}
+
+/// Reads the bytes of `part` from `reader`, which is at `offset` in the object.
+fn read_part(reader: &mut Read, offset: &mut u64, part: &PartAction) -> Result<Vec<u8>, RemoteError> {
+  if part.pos < *offset {
+    return Err(RemoteError::Upload(format!("multipart: part at {} overlaps the previous one", part.pos)));
+  }
+
+  std::io::copy(&mut std::io::Read::take(&mut *reader, part.pos - *offset), &mut std::io::sink())?;
+
+  let mut content = Vec::new();
+  match part.size {
+    Some(size) => std::io::Read::take(&mut *reader, size).read_to_end(&mut content)?,
+    None => reader.read_to_end(&mut content)?,
+  };
+
+  *offset = part.pos + content.len() as u64;
+  Ok(content)
+}
@@ -3,6 +3,10 @@ use crate::remote::LfsRemote;
 use crate::remote::Write;
 use crate::remote::dto::BatchResponse;
 
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
 use reqwest::header::HeaderMap;
 use url::Url;
 
@@ -17,12 +21,10 @@ use crate::remote::MEDIA_TYPE;
 use crate::remote::dto::*;
 
 const USER_AGENT: &str = "gx-lfs/0.0.0";
+const TUS_VERSION: &str = "1.0.0";
 
 trait ReqwestExt {
-  async fn or_err<T: FnOnce(String) -> RemoteError>(
-    self,
-    or_else: T,
-  ) -> Result<reqwest::Response, RemoteError>;
+  async fn or_err(self) -> Result<reqwest::Response, RemoteError>;
 }
 
 pub struct ReqwestLfsClient {
@@ -40,52 +42,46 @@ impl ReqwestLfsClient {
   pub fn headers(self, headers: HeaderMap) -> Self {
     Self { headers: Some(headers), ..self }
   }
-}
-
-impl ReqwestExt for Result<reqwest::Response, reqwest::Error> {
-  async fn or_err<T: FnOnce(String) -> RemoteError>(
-    self,
-    or_else: T,
-  ) -> Result<reqwest::Response, RemoteError> {
-    let res = self.map_err(|e| RemoteError::Custom(Box::new(e)))?;
-
-    if !res.status().is_success() {
-      use reqwest::StatusCode as S;
-
-      return match res.status() {
-        S::FORBIDDEN | S::UNAUTHORIZED => Err(RemoteError::AccessDenied),
-        S::NOT_FOUND => Err(RemoteError::NotFound),
-        _ => {
-          let status = res.status();
-          let body = res.text().await.unwrap_or_default();
-          Err(or_else(format!("{} - {}", status, body)))
-        }
-      };
-    }
-
-    Ok(res)
-  }
-}
 
-#[async_trait]
-impl LfsRemote for ReqwestLfsClient {
-  async fn batch(&self, req: BatchRequest) -> Result<BatchResponse, RemoteError> {
-    let mut batch_url = self.url.clone();
-    batch_url
+  /// A request to the lfs api below the endpoint url, with the client's credentials and headers.
+  fn api(&self, method: reqwest::Method, segments: &[&str]) -> Result<reqwest::RequestBuilder, RemoteError> {
+    let mut url = self.url.clone();
+    url
       .path_segments_mut()
       .map_err(|_| RemoteError::UrlParse(url::ParseError::RelativeUrlWithoutBase))?
       .pop_if_empty()
-      .push("objects")
-      .push("batch");
+      .extend(segments);
 
-    let mut request = self
+    let request = self
       .client
-      .post(batch_url)
+      .request(method, url)
       .header("User-Agent", USER_AGENT)
       .header("Accept", MEDIA_TYPE)
-      .header("Content-Type", MEDIA_TYPE)
-      .json(&req);
+      .header("Content-Type", MEDIA_TYPE);
+
+    Ok(self.credentials(request))
+  }
+
+  /// A request for a batch action with the action's headers. Actions the server didn't pre-authenticate
+  /// also get the client's credentials, but only when they point at the endpoint's own origin, so nothing
+  /// leaks to storage hosts elsewhere.
+  fn action(&self, method: reqwest::Method, action: &ObjectAction) -> reqwest::RequestBuilder {
+    let mut request = self.client.request(method, &action.href).header("User-Agent", USER_AGENT);
+
+    let same_origin = Url::parse(&action.href).is_ok_and(|url| url.origin() == self.url.origin());
+    let has_auth = action.header.keys().any(|k| k.eq_ignore_ascii_case("authorization"));
+    if !action.authenticated && same_origin && !has_auth {
+      request = self.credentials(request);
+    }
+
+    for (key, value) in action.header.iter() {
+      request = request.header(key, value);
+    }
+
+    request
+  }
 
+  fn credentials(&self, mut request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
     if let Some(token) = &self.access_token {
       request = request.basic_auth("oauth2", Some(token));
     }
@@ -94,7 +90,70 @@ impl LfsRemote for ReqwestLfsClient {
       request = request.headers(headers.clone());
     }
 
-    let res = request.send().await.or_err(RemoteError::Batch).await?;
+    request
+  }
+}
+
+impl ReqwestExt for Result<reqwest::Response, reqwest::Error> {
+  async fn or_err(self) -> Result<reqwest::Response, RemoteError> {
+    let res = self.map_err(|e| RemoteError::Custom(Box::new(e)))?;
+
+    if res.status().is_success() {
+      return Ok(res);
+    }
+
+    use reqwest::StatusCode as S;
+
+    let status = res.status();
+    let reset_at = rate_limit_reset(res.headers());
+    let error = error_response(status, &res.text().await.unwrap_or_default());
+
+    Err(match status {
+      S::NOT_FOUND => RemoteError::NotFound(error),
+      S::FORBIDDEN | S::UNAUTHORIZED => RemoteError::AccessDenied(error),
+      S::UNPROCESSABLE_ENTITY => RemoteError::Validation(error),
+      S::TOO_MANY_REQUESTS => RemoteError::RateLimited { error, reset_at },
+      S::INSUFFICIENT_STORAGE => RemoteError::InsufficientStorage(error),
+      S::NOT_IMPLEMENTED => RemoteError::NotImplemented(error),
+      _ => RemoteError::Http { status: status.as_u16(), error },
+    })
+  }
+}
+
+/// The server's json error, or one made of the status and whatever else the body says.
+fn error_response(status: reqwest::StatusCode, body: &str) -> ErrorResponse {
+  serde_json::from_str::<ErrorResponse>(body).unwrap_or_else(|_| ErrorResponse {
+    message: match body.trim() {
+      "" => status.canonical_reason().unwrap_or_default().to_string(),
+      body => body.to_string(),
+    },
+    documentation_url: None,
+    request_id: None,
+  })
+}
+
+/// When a rate limited request may be retried, from `Retry-After` seconds or http date, or a
+/// `RateLimit-Reset` / `X-RateLimit-Reset` unix time.
+fn rate_limit_reset(headers: &HeaderMap) -> Option<SystemTime> {
+  let header = |name: &str| Some(headers.get(name)?.to_str().ok()?.trim());
+  let secs = |name: &str| header(name)?.parse::<u64>().ok();
+
+  if let Some(retry_after) = header("Retry-After") {
+    match retry_after.parse::<u64>() {
+      Ok(secs) => return Some(SystemTime::now() + Duration::from_secs(secs)),
+      Err(_) => return httpdate::parse_http_date(retry_after).ok(),
+    }
+  }
+
+  let reset = secs("RateLimit-Reset").or_else(|| secs("X-RateLimit-Reset"))?;
+  Some(UNIX_EPOCH + Duration::from_secs(reset))
+}
+
+#[async_trait]
+impl LfsRemote for ReqwestLfsClient {
+  async fn batch(&self, req: BatchRequest) -> Result<BatchResponse, RemoteError> {
+    let request = self.api(reqwest::Method::POST, &["objects", "batch"])?.json(&req);
+    let res = request.send().await.or_err().await?;
     let res = res.json::<BatchResponse>().await.map_err(|e| RemoteError::Custom(Box::new(e)))?;
 
     if res.objects.is_empty() {
@@ -107,15 +166,7 @@ impl LfsRemote for ReqwestLfsClient {
   async fn download(&self, action: &ObjectAction, to: &mut Write) -> Result<Pointer, RemoteError> {
     use futures::StreamExt;
 
-    let mut req = self.client.get(&action.href);
-
-    for (key, value) in action.header.iter() {
-      req = req.header(key, value);
-    }
-
-    req = req.header("User-Agent", USER_AGENT);
-
-    let res = req.send().await.or_err(RemoteError::Download).await?;
+    let res = self.action(reqwest::Method::GET, action).send().await.or_err().await?;
 
     let mut bytes = res.bytes_stream();
     let mut total = 0;
@@ -134,34 +185,88 @@ impl LfsRemote for ReqwestLfsClient {
   }
 
   async fn upload(&self, action: &ObjectAction, blob: &[u8]) -> Result<(), RemoteError> {
-    let mut req = self.client.put(&action.href);
-
-    for (key, value) in action.header.iter() {
-      req = req.header(key, value);
-    }
-
-    req = req.header("User-Agent", USER_AGENT);
-    req.body(blob.to_owned()).send().await.or_err(RemoteError::Upload).await?;
+    let req = self.action(reqwest::Method::PUT, action);
+    req.body(blob.to_owned()).send().await.or_err().await?;
 
     Ok(())
   }
 
   async fn verify(&self, action: &ObjectAction, pointer: &Pointer) -> Result<(), RemoteError> {
-    let mut req = self.client.post(&action.href);
-
-    for (key, value) in action.header.iter() {
-      req = req.header(key, value);
-    }
-
-    req = req.header("User-Agent", USER_AGENT);
-
-    req
+    self
+      .action(reqwest::Method::POST, action)
       .json(&BatchObject { oid: pointer.hex(), size: pointer.size() as u64 })
       .send()
       .await
-      .or_err(RemoteError::Verify)
+      .or_err()
       .await?;
 
     Ok(())
   }
+
+  fn supports_multipart(&self) -> bool {
+    true
+  }
+
+  async fn multipart(&self, action: &MultipartAction, pointer: &Pointer) -> Result<(), RemoteError> {
+    let method = action.method.as_deref().unwrap_or("POST");
+    let method = reqwest::Method::from_bytes(method.as_bytes())
+      .map_err(|_| RemoteError::Upload(format!("invalid multipart method '{}'", method)))?;
+
+    let req = self.action(method, &action.action);
+    let req = match &action.body {
+      Some(body) => req.body(body.clone()),
+      None => req
+        .header("Content-Type", MEDIA_TYPE)
+        .json(&BatchObject { oid: pointer.hex(), size: pointer.size() as u64 }),
+    };
+
+    req.send().await.or_err().await?;
+
+    Ok(())
+  }
+
+  fn supports_tus(&self) -> bool {
+    true
+  }
+
+  async fn upload_offset(&self, action: &ObjectAction) -> Result<u64, RemoteError> {
+    let req = self.action(reqwest::Method::HEAD, action).header("Tus-Resumable", TUS_VERSION);
+
+    // An upload the server hasn't heard of yet starts from the beginning.
+    let res = match req.send().await.or_err().await {
+      Ok(res) => res,
+      Err(RemoteError::NotFound(_)) => return Ok(0),
+      Err(e) => return Err(e),
+    };
+
+    let offset = res.headers().get("Upload-Offset").and_then(|v| v.to_str().ok()).unwrap_or("0");
+    offset.parse().map_err(|_| RemoteError::Upload(format!("invalid Upload-Offset '{}'", offset)))
+  }
+
+  async fn upload_at(&self, action: &ObjectAction, offset: u64, blob: Vec<u8>) -> Result<(), RemoteError> {
+    let req = self
+      .action(reqwest::Method::PATCH, action)
+      .header("Tus-Resumable", TUS_VERSION)
+      .header("Upload-Offset", offset.to_string())
+      .header("Content-Type", "application/offset+octet-stream");
+    req.body(blob).send().await.or_err().await?;
+
+    Ok(())
+  }
+
+  async fn lock(&self, req: LockRequest) -> Result<LockResponse, RemoteError> {
+    let res = self.api(reqwest::Method::POST, &["locks"])?.json(&req).send().await.or_err().await?;
+    res.json::<LockResponse>().await.map_err(|e| RemoteError::Custom(Box::new(e)))
+  }
+
+  async fn locks(&self, req: &LockListRequest) -> Result<LockListResponse, RemoteError> {
+    let res = self.api(reqwest::Method::GET, &["locks"])?.query(req).send().await.or_err().await?;
+    res.json::<LockListResponse>().await.map_err(|e| RemoteError::Custom(Box::new(e)))
+  }
+
+  async fn unlock(&self, id: &str, req: UnlockRequest) -> Result<UnlockResponse, RemoteError> {
+    let res =
+      self.api(reqwest::Method::POST, &["locks", id, "unlock"])?.json(&req).send().await.or_err().await?;
+    res.json::<UnlockResponse>().await.map_err(|e| RemoteError::Custom(Box::new(e)))
+  }
 }
@@ -0,0 +1,54 @@
+use std::io::Read as _;
+use std::time::Duration;
+
+use tracing::*;
+
+use crate::Pointer;
+use crate::remote::LfsClient;
+use crate::remote::LfsRemote;
+use crate::remote::ObjectAction;
+use crate::remote::RemoteError;
+
+const ATTEMPTS: usize = 5;
+
+impl<C: LfsRemote + Send + Sync> LfsClient<'_, C> {
+  /// Uploads an object with the `tus` protocol: each attempt asks the server how much of it already
+  /// arrived and only sends the rest, so an interrupted upload picks up where it stopped.
+  pub(super) async fn upload_resumable(
+    &self,
+    pointer: &Pointer,
+    action: &ObjectAction,
+  ) -> Result<(), RemoteError> {
+    let size = pointer.size() as u64;
+    let mut attempt = 1;
+
+    loop {
+      let result = async {
+        let offset = self.client.upload_offset(action).await?;
+        if offset >= size {
+          debug!(oid = %pointer.hex(), "tus: server already has every byte");
+          return Ok(());
+        }
+
+        let mut reader = self.store.open(pointer)?;
+        std::io::copy(&mut std::io::Read::take(&mut *reader, offset), &mut std::io::sink())?;
+        let mut content = Vec::with_capacity((size - offset) as usize);
+        reader.read_to_end(&mut content)?;
+
+        debug!(url = %action.href, offset, size, attempt, "tus: sending the rest of the object");
+        self.client.upload_at(action, offset, content).await
+      }
+      .await;
+
+      match result {
+        Ok(()) => return Ok(()),
+        Err(e) if attempt < ATTEMPTS => {
+          warn!(error = %e, attempt, "tus: upload interrupted, resuming");
+          attempt += 1;
+          futures_timer::Delay::new(Duration::from_millis(500)).await;
+        }
+        Err(e) => return Err(e),
+      }
+    }
+  }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use git2::ObjectType;
+use git2::Odb;
+use git2::Oid;
+use git2::Pathspec;
+use git2::PathspecFlags;
+use git2::Sort;
+use git2::TreeWalkMode;
+use git2::TreeWalkResult;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+use crate::pointer::POINTER_ROUGH_LEN;
+
+const DAY: i64 = 24 * 60 * 60;
+
+pub(crate) const INDEX_ENTRY_STAGE_MASK: u16 = 0x3000;
+
+/// Resolves blob ids to lfs pointers, reading only the object header for blobs that are too big or too
+/// small to be a pointer. Results are memoised, so a blob shared by many commits is parsed once.
+pub(crate) struct PointerScanner<'r> {
+  odb: Odb<'r>,
+  seen: HashMap<Oid, Option<Pointer>>,
+  trees: HashSet<Oid>,
+}
+
+impl<'r> PointerScanner<'r> {
+  pub fn new(repo: &'r git2::Repository) -> Result<Self, Error> {
+    Ok(Self { odb: repo.odb()?, seen: HashMap::new(), trees: HashSet::new() })
+  }
+
+  pub fn pointer(&mut self, oid: Oid) -> Result<Option<Pointer>, Error> {
+    if let Some(pointer) = self.seen.get(&oid) {
+      return Ok(*pointer);
+    }
+
+    let (size, kind) = self.odb.read_header(oid)?;
+
+    let pointer = if kind == ObjectType::Blob && POINTER_ROUGH_LEN.contains(&size) {
+      Pointer::from_str_short(self.odb.read(oid)?.data())
+    } else {
+      None
+    };
+
+    self.seen.insert(oid, pointer);
+    Ok(pointer)
+  }
+
+  pub fn header(&self, oid: Oid) -> Result<(usize, ObjectType), Error> {
+    Ok(self.odb.read_header(oid)?)
+  }
+
+  /// Adds every pointer in `tree` to `pointers`. Trees already collected by this scanner are skipped, so
+  /// walking many commits that share most of their trees stays cheap.
+  pub fn collect_tree(
+    &mut self,
+    tree: &git2::Tree<'_>,
+    pointers: &mut HashSet<Pointer>,
+  ) -> Result<(), Error> {
+    if !self.trees.insert(tree.id()) {
+      return Ok(());
+    }
+
+    tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+      let oid = entry.id();
+
+      match entry.kind() {
+        Some(ObjectType::Tree) if !self.trees.insert(oid) => return TreeWalkResult::Skip,
+        Some(ObjectType::Blob) => (),
+        _ => return TreeWalkResult::Ok,
+      }
+
+      if let Ok(Some(pointer)) = self.pointer(oid)
+        && pointers.insert(pointer)
+      {
+        debug!(blob = %oid, tree = %tree.id(), "found lfs-pointer!");
+      }
+
+      TreeWalkResult::Ok
+    })?;
+
+    Ok(())
+  }
+
+  /// Calls `f` for every blob in `tree` with its path relative to the tree root and the pointer it holds.
+  pub fn walk_tree(
+    &mut self,
+    tree: &git2::Tree<'_>,
+    f: impl FnMut(&str, Oid, Option<Pointer>),
+  ) -> Result<(), Error> {
+    self.walk(tree, false, f)
+  }
+
+  /// Like [`PointerScanner::walk_tree`], but skips trees this scanner has already walked or collected, so
+  /// only blobs under trees new to it are visited.
+  pub fn walk_new_trees(
+    &mut self,
+    tree: &git2::Tree<'_>,
+    f: impl FnMut(&str, Oid, Option<Pointer>),
+  ) -> Result<(), Error> {
+    if !self.trees.insert(tree.id()) {
+      return Ok(());
+    }
+
+    self.walk(tree, true, f)
+  }
+
+  fn walk(
+    &mut self,
+    tree: &git2::Tree<'_>,
+    skip_seen: bool,
+    mut f: impl FnMut(&str, Oid, Option<Pointer>),
+  ) -> Result<(), Error> {
+    tree.walk(TreeWalkMode::PreOrder, |dir, entry| {
+      let name = entry.name().unwrap_or_default();
+      match entry.kind() {
+        Some(ObjectType::Tree) if skip_seen && !self.trees.insert(entry.id()) => return TreeWalkResult::Skip,
+        Some(ObjectType::Blob) if !name.is_empty() => (),
+        _ => return TreeWalkResult::Ok,
+      }
+
+      match self.pointer(entry.id()) {
+        Ok(pointer) => f(&format!("{}{}", dir, name), entry.id(), pointer),
+        Err(e) => warn!("blob '{}' ({}{}) can't be read: {}", entry.id(), dir, name, e),
+      }
+
+      TreeWalkResult::Ok
+    })?;
+
+    Ok(())
+  }
+}
+
+/// Matches paths against include and exclude pathspecs; an empty include list matches everything.
+pub(crate) struct PathFilter {
+  include: Option<Pathspec>,
+  exclude: Option<Pathspec>,
+}
+
+impl PathFilter {
+  pub fn new(include: &[String], exclude: &[String]) -> Result<Self, Error> {
+    let pathspec = |patterns: &[String]| -> Result<Option<Pathspec>, Error> {
+      if patterns.is_empty() {
+        Ok(None)
+      } else {
+        Ok(Some(Pathspec::new(patterns.iter().map(String::as_str))?))
+      }
+    };
+
+    Ok(Self { include: pathspec(include)?, exclude: pathspec(exclude)? })
+  }
+
+  pub fn matches(&self, path: &Path) -> bool {
+    let included = self.include.as_ref().is_none_or(|spec| spec.matches_path(path, PathspecFlags::DEFAULT));
+    let excluded = self.exclude.as_ref().is_some_and(|spec| spec.matches_path(path, PathspecFlags::DEFAULT));
+    included && !excluded
+  }
+}
+
+/// Tips of local branches and remote-tracking refs committed within the last `days`.
+pub(crate) fn recent_ref_tips(repo: &git2::Repository, days: u32) -> Result<Vec<Oid>, Error> {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default();
+  let cutoff = now - days as i64 * DAY;
+
+  let mut tips = Vec::new();
+  for reference in repo.references()? {
+    let reference = reference?;
+    if !reference.is_branch() && !reference.is_remote() {
+      continue;
+    }
+
+    if let Ok(commit) = reference.peel_to_commit()
+      && commit.time().seconds() >= cutoff
+    {
+      tips.push(commit.id());
+    }
+  }
+
+  Ok(tips)
+}
+
+/// `tip` and its ancestors committed within `days` of it.
+pub(crate) fn recent_commits(repo: &git2::Repository, tip: Oid, days: u32) -> Result<Vec<Oid>, Error> {
+  if days == 0 {
+    return Ok(vec![tip]);
+  }
+
+  let cutoff = repo.find_commit(tip)?.time().seconds() - days as i64 * DAY;
+
+  let mut revwalk = repo.revwalk()?;
+  revwalk.set_sorting(Sort::TIME)?;
+  revwalk.push(tip)?;
+
+  let mut commits = Vec::new();
+  for commit in revwalk {
+    let commit = repo.find_commit(commit?)?;
+    if commit.time().seconds() < cutoff {
+      break;
+    }
+
+    commits.push(commit.id());
+  }
+
+  Ok(commits)
+}
@@ -0,0 +1,64 @@
+use std::sync::Mutex;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use crate::remote::Lock;
+use crate::remote::LockOwner;
+use crate::server::ServerError;
+
+pub trait LockStore: Send + Sync {
+  /// Locks `path` for `owner`; a path that's locked already fails with [`ServerError::LockExists`].
+  fn create(&self, path: &str, owner: &str) -> Result<Lock, ServerError>;
+
+  /// Every lock, oldest first.
+  fn list(&self) -> Result<Vec<Lock>, ServerError>;
+
+  fn remove(&self, id: &str) -> Result<Option<Lock>, ServerError>;
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryLockStore {
+  locks: Mutex<Vec<Lock>>,
+  next_id: AtomicU64,
+}
+
+impl MemoryLockStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl LockStore for MemoryLockStore {
+  fn create(&self, path: &str, owner: &str) -> Result<Lock, ServerError> {
+    let mut locks = self.locks.lock().unwrap();
+    if let Some(existing) = locks.iter().find(|l| l.path == path) {
+      return Err(ServerError::LockExists(existing.clone()));
+    }
+
+    let lock = Lock {
+      id: (self.next_id.fetch_add(1, Ordering::Relaxed) + 1).to_string(),
+      path: path.to_string(),
+      locked_at: rfc3339(super::sign::now()),
+      owner: LockOwner { name: owner.to_string() },
+    };
+
+    locks.push(lock.clone());
+    Ok(lock)
+  }
+
+  fn list(&self) -> Result<Vec<Lock>, ServerError> {
+    Ok(self.locks.lock().unwrap().clone())
+  }
+
+  fn remove(&self, id: &str) -> Result<Option<Lock>, ServerError> {
+    let mut locks = self.locks.lock().unwrap();
+    Ok(locks.iter().position(|l| l.id == id).map(|i| locks.remove(i)))
+  }
+}
+
+/// Formats unix seconds as an RFC 3339 UTC timestamp, the format the locks api uses for `locked_at`.
+pub(super) fn rfc3339(secs: u64) -> String {
+  humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(secs)).to_string()
+}
@@ -0,0 +1,366 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::*;
+use url::Url;
+
+use crate::Pointer;
+use crate::remote::*;
+use crate::store::ObjectStore;
+use crate::store::Read;
+
+pub use locks::LockStore;
+pub use locks::MemoryLockStore;
+pub use sign::Action;
+pub use sign::HrefSigner;
+pub use sign::SignedQuery;
+
+mod locks;
+mod sign;
+
+const DEFAULT_LOCK_LIMIT: usize = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+  #[error("credentials needed")]
+  Unauthorized,
+
+  #[error("access denied")]
+  Forbidden,
+
+  #[error("not found")]
+  NotFound,
+
+  #[error("the link is invalid or has expired")]
+  InvalidSignature,
+
+  #[error("already locked")]
+  LockExists(Lock),
+
+  #[error("unsupported hash algorithm '{0}'")]
+  UnsupportedHashAlgo(String),
+
+  #[error("none of the transfer adapters {0:?} is supported")]
+  UnsupportedTransfer(Vec<String>),
+
+  #[error("invalid request: {0}")]
+  Invalid(String),
+
+  #[error("object store: {0}")]
+  Store(#[from] crate::Error),
+}
+
+impl ServerError {
+  pub fn status(&self) -> u16 {
+    match self {
+      ServerError::Unauthorized => 401,
+      ServerError::Forbidden | ServerError::InvalidSignature => 403,
+      ServerError::NotFound => 404,
+      ServerError::LockExists(_) | ServerError::UnsupportedHashAlgo(_) => 409,
+      ServerError::Invalid(_)
+      | ServerError::UnsupportedTransfer(_)
+      | ServerError::Store(crate::Error::ObjectMismatch { .. }) => 422,
+      ServerError::Store(_) => 500,
+    }
+  }
+
+  /// The json body to answer with: the conflicting lock for [`ServerError::LockExists`], an error message
+  /// otherwise.
+  pub fn body(&self) -> serde_json::Value {
+    let message = self.to_string();
+    let body = match self {
+      ServerError::LockExists(lock) => {
+        serde_json::to_value(LockResponse { lock: lock.clone(), message: Some(message) })
+      }
+      _ => serde_json::to_value(ErrorResponse { message, documentation_url: None, request_id: None }),
+    };
+
+    body.unwrap_or_default()
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+  Download,
+  Upload,
+}
+
+/// Decides who sent a request from its `Authorization` header and whether they may have `access` to the
+/// ref the client named, if any. Lock requests need [`Access::Upload`].
+pub trait Auth: Send + Sync {
+  fn authorize(
+    &self,
+    authorization: Option<&str>,
+    access: Access,
+    ref_name: Option<&str>,
+  ) -> Result<String, ServerError>;
+}
+
+impl<F> Auth for F
+where
+  F: Fn(Option<&str>, Access, Option<&str>) -> Result<String, ServerError> + Send + Sync,
+{
+  fn authorize(
+    &self,
+    authorization: Option<&str>,
+    access: Access,
+    ref_name: Option<&str>,
+  ) -> Result<String, ServerError> {
+    self(authorization, access, ref_name)
+  }
+}
+
+/// Lets everyone in as `anonymous`.
+struct Anonymous;
+
+impl Auth for Anonymous {
+  fn authorize(&self, _: Option<&str>, _: Access, _: Option<&str>) -> Result<String, ServerError> {
+    Ok("anonymous".to_string())
+  }
+}
+
+/// The lfs api for one endpoint, independent of any http framework: each handler takes what a route
+/// carries and returns what to answer with, or a [`ServerError`] with its status and body.
+///
+/// Routes below the endpoint url:
+/// - `POST objects/batch` - [`LfsServer::batch`]
+/// - `GET objects/{oid}` - [`LfsServer::download`]
+/// - `PUT objects/{oid}` - [`LfsServer::upload`]
+/// - `POST objects/{oid}/verify` - [`LfsServer::verify`]
+/// - `POST locks`, `GET locks`, `POST locks/verify` and `POST locks/{id}/unlock` - the locks api
+///
+/// Transfer routes are authorized by their signed query instead of credentials.
+pub struct LfsServer {
+  url: Url,
+  store: Arc<dyn ObjectStore>,
+  locks: Arc<dyn LockStore>,
+  auth: Box<dyn Auth>,
+  signer: HrefSigner,
+}
+
+impl LfsServer {
+  /// A server for the endpoint at `url`, signing hrefs with `key`.
+  pub fn new(url: Url, store: Arc<dyn ObjectStore>, key: &[u8]) -> Self {
+    Self {
+      url,
+      store,
+      locks: Arc::new(MemoryLockStore::new()),
+      auth: Box::new(Anonymous),
+      signer: HrefSigner::new(key),
+    }
+  }
+
+  pub fn lock_store(self, locks: Arc<dyn LockStore>) -> Self {
+    Self { locks, ..self }
+  }
+
+  pub fn auth(self, auth: impl Auth + 'static) -> Self {
+    Self { auth: Box::new(auth), ..self }
+  }
+
+  /// How long the hrefs of a batch response stay valid.
+  pub fn expiry(self, expires_in: Duration) -> Self {
+    Self { signer: self.signer.with_expiry(expires_in), ..self }
+  }
+
+  pub fn batch(&self, authorization: Option<&str>, req: BatchRequest) -> Result<BatchResponse, ServerError> {
+    let access = match req.operation.as_str() {
+      "download" => Access::Download,
+      "upload" => Access::Upload,
+      operation => return Err(ServerError::Invalid(format!("unknown operation '{}'", operation))),
+    };
+
+    if let Some(algo) = req.hash_algo.as_deref().filter(|a| *a != "sha256") {
+      return Err(ServerError::UnsupportedHashAlgo(algo.to_string()));
+    }
+
+    // Clients that don't list any adapters only speak basic.
+    if !req.transfers.is_empty() && !req.transfers.iter().any(|t| t == "basic") {
+      return Err(ServerError::UnsupportedTransfer(req.transfers));
+    }
+
+    let user = self.auth.authorize(authorization, access, ref_name(req.ref_name.as_ref()))?;
+    debug!(user, operation = %req.operation, objects = req.objects.len(), "lfs server: batch");
+
+    let objects = req
+      .objects
+      .into_iter()
+      .map(|object| {
+        let (actions, error) = match self.object_actions(access, &object) {
+          Ok(actions) => (actions, None),
+          Err(e) => (None, Some(ObjectError { code: e.status() as u32, message: e.to_string() })),
+        };
+
+        BatchResponseObject { oid: object.oid, size: object.size, authenticated: Some(true), actions, error }
+      })
+      .collect();
+
+    Ok(BatchResponse { transfer: Some("basic".to_string()), objects, hash_algo: Some("sha256".to_string()) })
+  }
+
+  pub fn download(&self, oid: &str, query: &str) -> Result<Box<Read>, ServerError> {
+    let pointer = self.signer.verify(Action::Download, oid, &SignedQuery::parse(query)?)?;
+    if !self.store.contains(&pointer)? {
+      return Err(ServerError::NotFound);
+    }
+
+    Ok(self.store.open(&pointer)?)
+  }
+
+  /// Stores the request body, which must hash to the signed object.
+  pub fn upload(&self, oid: &str, query: &str, body: &mut dyn std::io::Read) -> Result<(), ServerError> {
+    let pointer = self.signer.verify(Action::Upload, oid, &SignedQuery::parse(query)?)?;
+
+    let mut writer = self.store.put_stream(&pointer)?;
+    std::io::copy(body, &mut writer).map_err(crate::Error::from)?;
+    writer.finish()?;
+
+    info!(oid, size = pointer.size(), "lfs server: object uploaded");
+    Ok(())
+  }
+
+  pub fn verify(&self, oid: &str, query: &str, req: BatchObject) -> Result<(), ServerError> {
+    let pointer = self.signer.verify(Action::Verify, oid, &SignedQuery::parse(query)?)?;
+    if req.oid != oid || req.size != pointer.size() as u64 {
+      return Err(ServerError::Invalid("object doesn't match the verified one".to_string()));
+    }
+
+    if !self.store.contains(&pointer)? {
+      return Err(ServerError::NotFound);
+    }
+
+    Ok(())
+  }
+
+  pub fn create_lock(
+    &self,
+    authorization: Option<&str>,
+    req: LockRequest,
+  ) -> Result<LockResponse, ServerError> {
+    let user = self.auth.authorize(authorization, Access::Upload, ref_name(req.ref_name.as_ref()))?;
+    let lock = self.locks.create(&req.path, &user)?;
+    info!(user, path = %lock.path, id = %lock.id, "lfs server: locked");
+    Ok(LockResponse { lock, message: None })
+  }
+
+  pub fn list_locks(
+    &self,
+    authorization: Option<&str>,
+    req: &LockListRequest,
+  ) -> Result<LockListResponse, ServerError> {
+    self.auth.authorize(authorization, Access::Download, req.ref_name.as_deref())?;
+
+    let mut locks = self.locks.list()?;
+    locks.retain(|l| {
+      req.path.as_ref().is_none_or(|path| l.path == *path) && req.id.as_ref().is_none_or(|id| l.id == *id)
+    });
+
+    let (locks, next_cursor) = page(locks, req.cursor.as_deref(), req.limit)?;
+    Ok(LockListResponse { locks, next_cursor })
+  }
+
+  /// Splits the locks into the caller's and everyone else's, as a client checks before pushing.
+  pub fn verify_locks(
+    &self,
+    authorization: Option<&str>,
+    req: VerifyLocksRequest,
+  ) -> Result<VerifyLocksResponse, ServerError> {
+    let user = self.auth.authorize(authorization, Access::Upload, ref_name(req.ref_name.as_ref()))?;
+
+    let (locks, next_cursor) = page(self.locks.list()?, req.cursor.as_deref(), req.limit)?;
+    let (ours, theirs) = locks.into_iter().partition(|l| l.owner.name == user);
+    Ok(VerifyLocksResponse { ours, theirs, next_cursor })
+  }
+
+  /// Releases a lock; someone else's lock is only released with `force`.
+  pub fn unlock(
+    &self,
+    authorization: Option<&str>,
+    id: &str,
+    req: UnlockRequest,
+  ) -> Result<UnlockResponse, ServerError> {
+    let user = self.auth.authorize(authorization, Access::Upload, ref_name(req.ref_name.as_ref()))?;
+
+    let lock = self.locks.list()?.into_iter().find(|l| l.id == id).ok_or(ServerError::NotFound)?;
+    if lock.owner.name != user && !req.force.unwrap_or_default() {
+      return Err(ServerError::Forbidden);
+    }
+
+    let lock = self.locks.remove(id)?.ok_or(ServerError::NotFound)?;
+    info!(user, path = %lock.path, id, "lfs server: unlocked");
+    Ok(UnlockResponse { lock, message: None })
+  }
+
+  fn object_actions(
+    &self,
+    access: Access,
+    object: &BatchObject,
+  ) -> Result<Option<ObjectActions>, ServerError> {
+    let pointer = pointer(&object.oid, object.size)?;
+    let present = self.store.contains(&pointer)?;
+
+    match access {
+      Access::Download if !present => Err(ServerError::NotFound),
+      Access::Download => Ok(Some(ObjectActions {
+        download: Some(self.href(Action::Download, &pointer)),
+        ..Default::default()
+      })),
+      // Objects the server already has need nothing more from the client.
+      Access::Upload if present => Ok(None),
+      Access::Upload => Ok(Some(ObjectActions {
+        upload: Some(self.href(Action::Upload, &pointer)),
+        verify: Some(self.href(Action::Verify, &pointer)),
+        ..Default::default()
+      })),
+    }
+  }
+
+  fn href(&self, action: Action, pointer: &Pointer) -> ObjectAction {
+    let query = self.signer.sign(action, pointer).to_query();
+    let base = self.url.as_str().trim_end_matches('/');
+    let href = match action {
+      Action::Verify => format!("{}/objects/{}/verify?{}", base, pointer.hex(), query),
+      _ => format!("{}/objects/{}?{}", base, pointer.hex(), query),
+    };
+
+    ObjectAction {
+      href,
+      header: Default::default(),
+      expires_in: Some(self.signer.expires_in().as_secs()),
+      expires_at: None,
+      authenticated: true,
+    }
+  }
+}
+
+fn pointer(oid: &str, size: u64) -> Result<Pointer, ServerError> {
+  let invalid = || ServerError::Invalid(format!("invalid object '{}'", oid));
+
+  let hash = hex::decode(oid).map_err(|_| invalid())?;
+  if hash.len() != 32 {
+    return Err(invalid());
+  }
+
+  Ok(Pointer::from_parts(&hash, size as usize))
+}
+
+fn ref_name(r: Option<&Ref>) -> Option<&str> {
+  r.map(|r| r.name.as_str())
+}
+
+fn page(
+  locks: Vec<Lock>,
+  cursor: Option<&str>,
+  limit: Option<u32>,
+) -> Result<(Vec<Lock>, Option<String>), ServerError> {
+  let start = match cursor {
+    Some(cursor) => {
+      cursor.parse::<usize>().map_err(|_| ServerError::Invalid(format!("invalid cursor '{}'", cursor)))?
+    }
+    None => 0,
+  };
+
+  let limit = limit.map_or(DEFAULT_LOCK_LIMIT, |l| l as usize).max(1);
+  let next_cursor = (start + limit < locks.len()).then(|| (start + limit).to_string());
+  Ok((locks.into_iter().skip(start).take(limit).collect(), next_cursor))
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use git2::AttrCheckFlags;
+use git2::Delta;
+use serde::Serialize;
+
+use crate::Error;
+use crate::Pointer;
+use crate::scan::INDEX_ENTRY_STAGE_MASK;
+use crate::scan::PointerScanner;
+use crate::store::ObjectStore;
+
+#[derive(Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LfsStatus {
+  /// Lfs files that differ between `HEAD` and the index.
+  pub staged: Vec<StagedFile>,
+  /// Objects in commits that haven't been pushed to the upstream.
+  pub unpushed: Vec<Pointer>,
+  /// Pointers in the index whose objects aren't in the local store.
+  pub missing: Vec<StatusFile>,
+  /// Index entries that match a `filter=lfs` pattern but are staged as regular blobs.
+  pub unconverted: Vec<PathBuf>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StagedFile {
+  pub path: PathBuf,
+  pub pointer: Pointer,
+  pub change: StagedChange,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StagedChange {
+  Added,
+  Modified,
+  Deleted,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusFile {
+  pub path: PathBuf,
+  pub pointer: Pointer,
+}
+
+pub(crate) fn status(
+  repo: &git2::Repository,
+  store: &dyn ObjectStore,
+  unpushed: Vec<Pointer>,
+) -> Result<LfsStatus, Error> {
+  let mut status = LfsStatus { unpushed, ..Default::default() };
+  let mut scanner = PointerScanner::new(repo)?;
+
+  let index = repo.index()?;
+  let head = match repo.head() {
+    Ok(head) => Some(head.peel_to_tree()?),
+    Err(e) if matches!(e.code(), git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound) => None,
+    Err(e) => return Err(e.into()),
+  };
+
+  let diff = repo.diff_tree_to_index(head.as_ref(), Some(&index), None)?;
+  for delta in diff.deltas() {
+    let (file, change) = match delta.status() {
+      Delta::Added | Delta::Copied | Delta::Renamed => (delta.new_file(), StagedChange::Added),
+      Delta::Modified | Delta::Typechange => (delta.new_file(), StagedChange::Modified),
+      Delta::Deleted => (delta.old_file(), StagedChange::Deleted),
+      _ => continue,
+    };
+
+    if let Some(path) = file.path()
+      && let Ok(Some(pointer)) = scanner.pointer(file.id())
+    {
+      status.staged.push(StagedFile { path: path.to_path_buf(), pointer, change });
+    }
+  }
+
+  for entry in index.iter().filter(|e| e.flags & INDEX_ENTRY_STAGE_MASK == 0) {
+    let path = PathBuf::from(std::str::from_utf8(&entry.path)?);
+
+    match scanner.pointer(entry.id) {
+      Ok(Some(pointer)) if !store.contains(&pointer)? => status.missing.push(StatusFile { path, pointer }),
+      Ok(Some(_)) => (),
+      Ok(None) => {
+        let is_lfs = repo.get_attr(Path::new(&path), "filter", AttrCheckFlags::default())? == Some("lfs");
+        if is_lfs && scanner.header(entry.id)?.0 > 0 {
+          status.unconverted.push(path);
+        }
+      }
+      Err(_) => (),
+    }
+  }
+
+  Ok(status)
+}
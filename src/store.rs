@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Cursor;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use sha2::Digest;
+use sha2::Sha256;
+use tracing::*;
+
+use crate::Error;
+use crate::Pointer;
+
+pub type Read = dyn std::io::Read + Send;
+pub type Objects<'a> = Box<dyn Iterator<Item = Result<Pointer, Error>> + 'a>;
+
+pub trait ObjectWriter: Write + Send {
+  fn finish(self: Box<Self>) -> Result<(), Error>;
+}
+
+pub trait ObjectStore: std::fmt::Debug + Send + Sync {
+  fn contains(&self, pointer: &Pointer) -> Result<bool, Error>;
+  fn open(&self, pointer: &Pointer) -> Result<Box<Read>, Error>;
+  fn put_stream(&self, pointer: &Pointer) -> Result<Box<dyn ObjectWriter>, Error>;
+  fn remove(&self, pointer: &Pointer) -> Result<(), Error>;
+  fn iter(&self) -> Result<Objects<'_>, Error>;
+
+  /// The directory objects are kept in as files laid out by [`Pointer::path`], for stores that have one.
+  fn object_dir(&self) -> Option<&Path> {
+    None
+  }
+
+  /// Takes a corrupt object out of the store. Stores without a place to keep bad objects just drop them.
+  fn quarantine(&self, pointer: &Pointer) -> Result<(), Error> {
+    self.remove(pointer)
+  }
+
+  fn put_bytes(&self, pointer: &Pointer, bytes: &[u8]) -> Result<(), Error> {
+    let mut writer = self.put_stream(pointer)?;
+    writer.write_all(bytes)?;
+    writer.finish()
+  }
+
+  fn read_to_vec(&self, pointer: &Pointer) -> Result<Vec<u8>, Error> {
+    let mut content = Vec::with_capacity(pointer.size());
+    std::io::Read::read_to_end(&mut self.open(pointer)?, &mut content)?;
+    Ok(content)
+  }
+}
+
+/// Resolves the lfs storage directory like upstream git-lfs does: `lfs.storage` if it's set (relative paths
+/// are taken from the common git dir), otherwise `lfs` inside the common git dir, so linked worktrees share
+/// one set of objects.
+pub fn storage_dir(repo: &git2::Repository) -> Result<PathBuf, Error> {
+  let common_dir = repo.commondir();
+
+  let storage = match repo.config()?.get_path("lfs.storage") {
+    Ok(storage) => storage,
+    Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(common_dir.join("lfs")),
+    Err(e) => return Err(e.into()),
+  };
+
+  debug!(storage = %storage.display(), "using lfs.storage from config");
+  Ok(if storage.is_absolute() { storage } else { common_dir.join(storage) })
+}
+
+#[derive(Debug, Clone)]
+pub struct FsObjectStore {
+  root: PathBuf,
+  alternates: Vec<PathBuf>,
+}
+
+impl FsObjectStore {
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self { root: root.into(), alternates: Vec::new() }
+  }
+
+  pub fn for_repo(repo: &git2::Repository) -> Result<Self, Error> {
+    let store = Self::new(storage_dir(repo)?.join("objects"));
+    Ok(store.with_alternates(alternates(repo)?))
+  }
+
+  /// Adds read-only object directories that are looked up before an object is considered missing. Objects
+  /// found there are hardlinked into this store when opened, or copied and verified like any other write
+  /// when they can't be.
+  pub fn with_alternates(mut self, alternates: impl IntoIterator<Item = PathBuf>) -> Self {
+    self.alternates.extend(alternates.into_iter().filter(|alternate| *alternate != self.root));
+    self
+  }
+
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  pub fn alternates(&self) -> &[PathBuf] {
+    &self.alternates
+  }
+
+  pub fn object_path(&self, pointer: &Pointer) -> PathBuf {
+    self.root.join(pointer.path())
+  }
+
+  fn find_in_alternates(&self, pointer: &Pointer) -> Option<PathBuf> {
+    self.alternates.iter().map(|alternate| alternate.join(pointer.path())).find(|path| path.exists())
+  }
+
+  fn materialize(&self, pointer: &Pointer, from: &Path) -> Result<PathBuf, Error> {
+    let path = self.object_path(pointer);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+
+    if let Err(e) = std::fs::hard_link(from, &path) {
+      if e.kind() == std::io::ErrorKind::AlreadyExists {
+        return Ok(path);
+      }
+
+      debug!(from = %from.display(), error = %e, "can't hardlink lfs object from alternate, copying");
+      let mut writer = self.put_stream(pointer)?;
+      std::io::copy(&mut File::open(from)?, &mut writer)?;
+      writer.finish()?;
+    }
+
+    info!(from = %from.display(), path = %path.display(), "materialized lfs object from alternate");
+    Ok(path)
+  }
+}
+
+impl Pointer {
+  /// Writes the object into `absolute_object_dir` laid out like an [`FsObjectStore`], through a verified temp
+  /// file.
+  pub fn write_blob_bytes(&self, absolute_object_dir: &Path, bytes: &[u8]) -> Result<(), Error> {
+    FsObjectStore::new(absolute_object_dir).put_bytes(self, bytes)
+  }
+}
+
+/// A temp file name next to `path` that no other writer in this or another process uses.
+fn temp_path(path: &Path, pointer: &Pointer) -> PathBuf {
+  static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+  path.with_file_name(format!(
+    ".{}.{}-{}.tmp",
+    pointer.hex(),
+    std::process::id(),
+    TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+  ))
+}
+
+/// Reads `lfs.alternates` entries; relative paths are taken from the common git dir.
+fn alternates(repo: &git2::Repository) -> Result<Vec<PathBuf>, Error> {
+  let config = repo.config()?;
+  let mut entries = match config.multivar("lfs.alternates", None) {
+    Ok(entries) => entries,
+    Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok(Vec::new()),
+    Err(e) => return Err(e.into()),
+  };
+
+  let mut alternates = Vec::new();
+  while let Some(entry) = entries.next() {
+    let path = PathBuf::from(entry?.value()?);
+    alternates.push(if path.is_absolute() { path } else { repo.commondir().join(path) });
+  }
+
+  Ok(alternates)
+}
+
+impl ObjectStore for FsObjectStore {
+  fn contains(&self, pointer: &Pointer) -> Result<bool, Error> {
+    Ok(self.object_path(pointer).exists() || self.find_in_alternates(pointer).is_some())
+  }
+
+  fn open(&self, pointer: &Pointer) -> Result<Box<Read>, Error> {
+    let path = self.object_path(pointer);
+
+    if !path.exists()
+      && let Some(alternate) = self.find_in_alternates(pointer)
+    {
+      return Ok(Box::new(File::open(self.materialize(pointer, &alternate)?)?));
+    }
+
+    Ok(Box::new(File::open(path)?))
+  }
+
+  fn put_stream(&self, pointer: &Pointer) -> Result<Box<dyn ObjectWriter>, Error> {
+    let path = self.object_path(pointer);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+
+    let temp = temp_path(&path, pointer);
+    info!(path = %path.display(), "writing lfs object");
+
+    let file = File::options().create_new(true).write(true).open(&temp)?;
+
+    Ok(Box::new(FsObjectWriter {
+      file: BufWriter::new(file),
+      hasher: Sha256::new(),
+      written: 0,
+      expected: *pointer,
+      temp,
+      path,
+      finished: false,
+    }))
+  }
+
+  fn object_dir(&self) -> Option<&Path> {
+    Some(&self.root)
+  }
+
+  fn remove(&self, pointer: &Pointer) -> Result<(), Error> {
+    match std::fs::remove_file(self.object_path(pointer)) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+      _ => Ok(()),
+    }
+  }
+
+  /// Moves the object into `lfs/bad`, next to the objects directory, like upstream `git lfs fsck` does.
+  fn quarantine(&self, pointer: &Pointer) -> Result<(), Error> {
+    let bad_dir = self.root.parent().unwrap_or(&self.root).join("bad");
+    std::fs::create_dir_all(&bad_dir)?;
+
+    let bad_path = bad_dir.join(pointer.hex());
+    info!(path = %bad_path.display(), "moving corrupt lfs object");
+    std::fs::rename(self.object_path(pointer), bad_path)?;
+    Ok(())
+  }
+
+  fn iter(&self) -> Result<Objects<'_>, Error> {
+    if !self.root.exists() {
+      return Ok(Box::new(std::iter::empty()));
+    }
+
+    let mut objects = Vec::new();
+
+    for first in std::fs::read_dir(&self.root)? {
+      let first = first?;
+      if !first.file_type()?.is_dir() {
+        continue;
+      }
+
+      for second in std::fs::read_dir(first.path())? {
+        let second = second?;
+        if !second.file_type()?.is_dir() {
+          continue;
+        }
+
+        for object in std::fs::read_dir(second.path())? {
+          let object = object?;
+          let name = object.file_name();
+
+          let mut hash = [0; 32];
+          if name.len() != 64 || hex::decode_to_slice(name.as_encoded_bytes(), &mut hash).is_err() {
+            trace!(path = %object.path().display(), "skipping non-object file");
+            continue;
+          }
+
+          objects.push(Ok(Pointer::from_parts(&hash, object.metadata()?.len() as usize)));
+        }
+      }
+    }
+
+    Ok(Box::new(objects.into_iter()))
+  }
+}
+
+/// Writes into a temp file next to the final object path, which is fsynced, checked against the pointer and
+/// renamed into place on `finish`. Dropping the writer without finishing removes the temp file, so readers
+/// never see a truncated object.
+struct FsObjectWriter {
+  file: BufWriter<File>,
+  hasher: Sha256,
+  written: usize,
+  expected: Pointer,
+  temp: PathBuf,
+  path: PathBuf,
+  finished: bool,
+}
+
+impl Write for FsObjectWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let n = self.file.write(buf)?;
+    self.hasher.update(&buf[..n]);
+    self.written += n;
+    Ok(n)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.file.flush()
+  }
+}
+
+impl ObjectWriter for FsObjectWriter {
+  fn finish(mut self: Box<Self>) -> Result<(), Error> {
+    self.file.flush()?;
+    self.file.get_ref().sync_all()?;
+
+    let actual = Pointer::from_parts(self.hasher.finalize_reset().as_slice(), self.written);
+    if actual != self.expected {
+      return Err(Error::ObjectMismatch { expected: self.expected, actual });
+    }
+
+    match std::fs::rename(&self.temp, &self.path) {
+      Ok(()) => self.finished = true,
+      Err(_) if self.path.exists() => {
+        debug!(path = %self.path.display(), "lfs object was written concurrently, keeping the existing one");
+      }
+      Err(e) => return Err(e.into()),
+    }
+
+    if let Some(parent) = self.path.parent()
+      && let Ok(dir) = File::open(parent)
+    {
+      let _ = dir.sync_all();
+    }
+
+    Ok(())
+  }
+}
+
+impl Drop for FsObjectWriter {
+  fn drop(&mut self) {
+    if !self.finished
+      && let Err(e) = std::fs::remove_file(&self.temp)
+    {
+      warn!(path = %self.temp.display(), error = %e, "can't remove temporary lfs object");
+    }
+  }
+}
+
+type MemoryObjects = HashMap<[u8; 32], Arc<[u8]>>;
+
+#[derive(Debug, Default, Clone)]
+pub struct MemoryObjectStore {
+  objects: Arc<Mutex<MemoryObjects>>,
+}
+
+impl MemoryObjectStore {
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+impl ObjectStore for MemoryObjectStore {
+  fn contains(&self, pointer: &Pointer) -> Result<bool, Error> {
+    Ok(self.objects.lock().unwrap().contains_key(pointer.hash()))
+  }
+
+  fn open(&self, pointer: &Pointer) -> Result<Box<Read>, Error> {
+    let objects = self.objects.lock().unwrap();
+    let content = objects.get(pointer.hash()).ok_or_else(|| {
+      std::io::Error::new(std::io::ErrorKind::NotFound, format!("object {} not found", pointer))
+    })?;
+
+    Ok(Box::new(Cursor::new(Arc::clone(content))))
+  }
+
+  fn put_stream(&self, pointer: &Pointer) -> Result<Box<dyn ObjectWriter>, Error> {
+    Ok(Box::new(MemoryObjectWriter {
+      objects: Arc::clone(&self.objects),
+      expected: *pointer,
+      content: Vec::with_capacity(pointer.size()),
+    }))
+  }
+
+  fn remove(&self, pointer: &Pointer) -> Result<(), Error> {
+    self.objects.lock().unwrap().remove(pointer.hash());
+    Ok(())
+  }
+
+  fn iter(&self) -> Result<Objects<'_>, Error> {
+    let objects = self.objects.lock().unwrap();
+    let pointers =
+      objects.iter().map(|(hash, content)| Ok(Pointer::from_parts(hash, content.len()))).collect::<Vec<_>>();
+
+    Ok(Box::new(pointers.into_iter()))
+  }
+}
+
+struct MemoryObjectWriter {
+  objects: Arc<Mutex<MemoryObjects>>,
+  expected: Pointer,
+  content: Vec<u8>,
+}
+
+impl Write for MemoryObjectWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self.content.write(buf)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+impl ObjectWriter for MemoryObjectWriter {
+  fn finish(self: Box<Self>) -> Result<(), Error> {
+    let actual = Pointer::from_blob_bytes(&self.content)?;
+    if actual != self.expected {
+      return Err(Error::ObjectMismatch { expected: self.expected, actual });
+    }
+
+    self.objects.lock().unwrap().insert(*self.expected.hash(), self.content.into());
+    Ok(())
+  }
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use crate::repo;
+use crate::sandbox;
+
+#[rstest]
+fn lfs_checkout_materializes_dangling_pointers(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let object_dir = repo.path().join("lfs/objects");
+  let sig = repo.signature()?;
+
+  let files: [(&str, &[u8]); 3] =
+    [("a.bin", b"first object"), ("media/b.bin", b"second object"), ("media/c.bin", b"third object")];
+
+  std::fs::create_dir_all(workdir.join("media"))?;
+  for (path, content) in files {
+    std::fs::write(workdir.join(path), content)?;
+  }
+
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  index.write()?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])?;
+
+  for (path, content) in files {
+    std::fs::write(workdir.join(path), Pointer::from_blob_bytes(content)?.as_bytes()?)?;
+  }
+
+  let missing = Pointer::from_blob_bytes(b"third object")?;
+  std::fs::remove_file(object_dir.join(missing.path()))?;
+
+  let report = repo.lfs_checkout(&["media"])?;
+  assert_eq!(report.updated, [Path::new("media/b.bin")]);
+  assert_eq!(report.missing, [Path::new("media/c.bin")]);
+  assert_eq!(std::fs::read(workdir.join("media/b.bin"))?, b"second object");
+  assert_eq!(std::fs::read(workdir.join("a.bin"))?, Pointer::from_blob_bytes(b"first object")?.as_bytes()?);
+
+  let report = repo.lfs_checkout(&[])?;
+  assert_eq!(report.updated, [Path::new("a.bin")]);
+  assert_eq!(std::fs::read(workdir.join("a.bin"))?, b"first object");
+
+  assert_eq!(repo.status_file(Path::new("a.bin"))?, git2::Status::CURRENT);
+  assert_eq!(repo.status_file(Path::new("media/b.bin"))?, git2::Status::CURRENT);
+
+  assert!(repo.lfs_checkout(&["a.bin", "media/b.bin"])?.updated.is_empty());
+
+  Ok(())
+}
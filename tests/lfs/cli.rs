@@ -0,0 +1,120 @@
+use std::path::Path;
+use std::process::Command;
+use std::process::Output;
+
+use git2_lfs::Pointer;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use crate::repo;
+use crate::sandbox;
+
+fn git_lfs(dir: &Path, args: &[&str], stdin: Option<&[u8]>) -> Result<Output, anyhow::Error> {
+  use std::io::Write;
+  use std::process::Stdio;
+
+  let mut child = Command::new(env!("CARGO_BIN_EXE_git-lfs"))
+    .args(args)
+    .current_dir(dir)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  child.stdin.take().unwrap().write_all(stdin.unwrap_or_default())?;
+  Ok(child.wait_with_output()?)
+}
+
+#[rstest]
+fn lfs_cli_track_untrack_and_filters(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+
+  let output = git_lfs(workdir, &["track", "*.psd", "my file.dat", "*.bin"], None)?;
+  assert!(output.status.success());
+  let stdout = String::from_utf8(output.stdout)?;
+  assert!(stdout.contains("Tracking \"*.psd\""), "{}", stdout);
+  assert!(stdout.contains("\"*.bin\" already supported"), "{}", stdout);
+
+  let attributes = std::fs::read_to_string(workdir.join(".gitattributes"))?;
+  assert!(attributes.contains("*.psd filter=lfs diff=lfs merge=lfs -text\n"), "{}", attributes);
+  assert!(attributes.contains("my[[:space:]]file.dat filter=lfs"), "{}", attributes);
+
+  git_lfs(workdir, &["untrack", "*.psd"], None)?;
+  let attributes = std::fs::read_to_string(workdir.join(".gitattributes"))?;
+  assert!(!attributes.contains("*.psd"), "{}", attributes);
+  assert!(attributes.contains("*.bin filter=lfs"), "{}", attributes);
+
+  let pointer = Pointer::from_blob_bytes(b"content")?;
+  let output = git_lfs(workdir, &["clean", "--", "file.bin"], Some(b"content"))?;
+  assert_eq!(output.stdout, pointer.as_bytes()?);
+
+  let output = git_lfs(workdir, &["smudge", "--", "file.bin"], Some(&pointer.as_bytes()?))?;
+  assert_eq!(output.stdout, b"content");
+
+  let output = git_lfs(workdir, &["no-such-command"], None)?;
+  assert_eq!(output.status.code(), Some(128));
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_cli_ls_files_and_pointer(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::write(workdir.join("a.bin"), b"a")?;
+  let mut index = repo.index()?;
+  index.add_path(Path::new("a.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])?;
+
+  let pointer = Pointer::from_blob_bytes(b"a")?;
+  let output = git_lfs(workdir, &["ls-files", "--long"], None)?;
+  assert_eq!(String::from_utf8(output.stdout)?, format!("{} * a.bin\n", pointer.hex()));
+
+  std::fs::write(workdir.join("a.pointer"), pointer.as_bytes()?)?;
+  let output = git_lfs(workdir, &["pointer", "--file", "a.bin", "--pointer", "a.pointer"], None)?;
+  assert!(output.status.success());
+  assert_eq!(output.stdout, pointer.as_bytes()?);
+
+  std::fs::write(workdir.join("b.bin"), b"b")?;
+  let output = git_lfs(workdir, &["pointer", "--file=b.bin", "--pointer=a.pointer"], None)?;
+  assert_eq!(output.status.code(), Some(1));
+
+  let output = git_lfs(workdir, &["pointer", "--file", "a.bin", "--stdin"], Some(b"not a pointer"))?;
+  assert_eq!(output.status.code(), Some(2));
+
+  let output = git_lfs(workdir, &["pointer", "--file", "missing.bin"], None)?;
+  assert_eq!(output.status.code(), Some(3));
+
+  let output = git_lfs(workdir, &["pointer", "--check", "--stdin"], Some(&pointer.as_bytes()?))?;
+  assert!(output.status.success());
+
+  let loose = format!("version https://git-lfs.github.com/spec/v1\noid sha256:{}\n", pointer.hex());
+  let output = git_lfs(workdir, &["pointer", "--check", "--stdin", "--strict"], Some(loose.as_bytes()))?;
+  assert_eq!(output.status.code(), Some(2));
+
+  let output = git_lfs(workdir, &["pointer", "--check", "--file", "a.bin"], None)?;
+  assert_eq!(output.status.code(), Some(2));
+
+  let output = git_lfs(workdir, &["pointer", "--check"], None)?;
+  assert_eq!(output.status.code(), Some(128), "usage errors aren't reported as an invalid pointer");
+
+  let output = git_lfs(workdir, &["fsck", "--dry-run"], None)?;
+  assert!(output.status.success());
+
+  std::fs::write(repo.path().join("lfs/objects").join(pointer.path()), b"corrupt")?;
+  let output = git_lfs(workdir, &["fsck", "--dry-run"], None)?;
+  assert_eq!(output.status.code(), Some(4), "fsck problems have their own code");
+
+  let output = git_lfs(workdir, &[], None)?;
+  assert_eq!(output.status.code(), Some(128));
+
+  Ok(())
+}
@@ -0,0 +1,74 @@
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::remote::reqwest::ReqwestLfsClient;
+use git2_lfs::remote::*;
+use rstest::rstest;
+use tempfile::TempDir;
+use url::Url;
+
+use super::http::response;
+use super::http::serve;
+use super::mock::MockRemote;
+use crate::repo;
+use crate::sandbox;
+
+fn action(href: String, authenticated: bool) -> ObjectAction {
+  ObjectAction { href, header: Default::default(), expires_in: None, expires_at: None, authenticated }
+}
+
+#[tokio::test]
+async fn lfs_credentials_only_for_unauthenticated_same_origin_actions() -> Result<(), anyhow::Error> {
+  let (port, heads) = serve(vec![response("200 OK", &[], "hello"); 3]);
+  let endpoint = Url::parse(&format!("http://127.0.0.1:{}/repo.git/info/lfs", port))?;
+  let client = ReqwestLfsClient::new(endpoint, Some("secret".to_string()));
+
+  let mut sink = Vec::new();
+  client.download(&action(format!("http://127.0.0.1:{}/objects/1", port), false), &mut sink).await?;
+  assert!(heads.recv()?.contains("authorization: basic"), "unauthenticated actions need credentials");
+
+  client.download(&action(format!("http://127.0.0.1:{}/objects/2", port), true), &mut sink).await?;
+  assert!(!heads.recv()?.contains("authorization"), "the server authenticated the href itself");
+
+  // Another host name for the same listener is another origin as far as the client knows.
+  client.download(&action(format!("http://localhost:{}/objects/3", port), false), &mut sink).await?;
+  assert!(!heads.recv()?.contains("authorization"), "credentials leaked to another host");
+
+  Ok(())
+}
+
+#[test]
+fn lfs_batch_response_marks_authenticated_actions() -> Result<(), anyhow::Error> {
+  let mut res = serde_json::from_str::<BatchResponse>(
+    r#"{"objects": [
+      {"oid": "a", "size": 1, "authenticated": true, "actions": {"download": {"href": "https://a"}}},
+      {"oid": "b", "size": 1, "actions": {"download": {"href": "https://b"}}}
+    ]}"#,
+  )?;
+  res.mark_authenticated();
+
+  let authenticated =
+    |i: usize| res.objects[i].actions.as_ref().unwrap().download.as_ref().unwrap().authenticated;
+  assert!(authenticated(0));
+  assert!(!authenticated(1));
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_client_marks_authenticated_actions_of_any_remote(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let uploaded = Pointer::from_blob_bytes(b"uploaded")?;
+  repo.lfs_object_store()?.put_bytes(&uploaded, b"uploaded")?;
+
+  let remote = MockRemote::new(&[b"downloaded"]).with_authenticated();
+  let client = LfsClient::new(&repo, remote.clone())?;
+  client.push(&[uploaded]).await?;
+  client.pull(&[Pointer::from_blob_bytes(b"downloaded")?]).await?;
+
+  assert_eq!(remote.actions_authenticated(), [true, true]);
+
+  Ok(())
+}
@@ -0,0 +1,114 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::remote::LfsClient;
+use git2_lfs::remote::custom::CustomTransfer;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use super::mock::MockRemote;
+use crate::repo;
+use crate::sandbox;
+
+/// Serves downloads from and stores uploads in the directory it's given, one file per oid.
+const AGENT: &str = r#"#!/bin/sh
+store="$1"
+while IFS= read -r line; do
+  event=$(printf '%s' "$line" | sed -n 's/.*"event":"\([a-z]*\)".*/\1/p')
+  oid=$(printf '%s' "$line" | sed -n 's/.*"oid":"\([0-9a-f]*\)".*/\1/p')
+  case "$event" in
+    init)
+      echo '{}'
+      ;;
+    download)
+      cp "$store/$oid" "$store/$oid.part"
+      printf '{"event":"progress","oid":"%s","bytesSoFar":1,"bytesSinceLast":1}\n' "$oid"
+      printf '{"event":"complete","oid":"%s","path":"%s"}\n' "$oid" "$store/$oid.part"
+      ;;
+    upload)
+      path=$(printf '%s' "$line" | sed -n 's/.*"path":"\([^"]*\)".*/\1/p')
+      if [ -e "$store/$oid.reject" ]; then
+        printf '{"event":"complete","oid":"%s","error":{"code":500,"message":"rejected"}}\n' "$oid"
+      else
+        cp "$path" "$store/$oid"
+        printf '%s' "$path" > "$store/$oid.path"
+        printf '{"event":"complete","oid":"%s"}\n' "$oid"
+      fi
+      ;;
+    terminate)
+      exit 0
+      ;;
+  esac
+done
+"#;
+
+fn agent(dir: &Path) -> Result<(PathBuf, PathBuf), anyhow::Error> {
+  let script = dir.join("agent.sh");
+  std::fs::write(&script, AGENT)?;
+  std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+
+  let store = dir.join("agent-store");
+  std::fs::create_dir_all(&store)?;
+  Ok((script, store))
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_custom_transfer_agent(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let (script, store) = agent(_sandbox.path())?;
+
+  let mut config = repo.config()?;
+  config.set_str("lfs.customtransfer.p2p.path", script.to_str().unwrap())?;
+  config.set_str("lfs.customtransfer.p2p.args", store.to_str().unwrap())?;
+  config.set_str("lfs.customtransfer.other.args", "--unused")?;
+  let transfer = CustomTransfer::from_config(&repo.config()?, "p2p")?.unwrap();
+  assert!(CustomTransfer::from_config(&repo.config()?, "missing")?.is_none());
+
+  let transfers = CustomTransfer::all_from_config(&repo.config()?)?;
+  assert_eq!(
+    transfers.iter().map(|t| t.name()).collect::<Vec<_>>(),
+    ["p2p"],
+    "agents without a path are skipped"
+  );
+
+  let remote = MockRemote::new(&[b"remote object"]).with_transfer("p2p");
+  let client = LfsClient::new(&repo, remote.clone())?.custom_transfer(transfer);
+
+  let downloaded = Pointer::from_blob_bytes(b"remote object")?;
+  std::fs::write(store.join(downloaded.hex()), b"remote object")?;
+  client.pull(&[downloaded]).await?;
+  assert_eq!(repo.lfs_object_store()?.read_to_vec(&downloaded)?, b"remote object");
+
+  let uploaded = Pointer::from_blob_bytes(b"local object")?;
+  repo.lfs_object_store()?.put_bytes(&uploaded, b"local object")?;
+  client.push(&[uploaded]).await?;
+  assert_eq!(std::fs::read(store.join(uploaded.hex()))?, b"local object");
+  assert!(!remote.has(&uploaded), "the basic adapter shouldn't have been used");
+
+  let object_path = repo.path().join("lfs/objects").join(uploaded.path());
+  assert_eq!(
+    std::fs::read_to_string(store.join(format!("{}.path", uploaded.hex())))?,
+    object_path.to_str().unwrap()
+  );
+
+  let rejected = Pointer::from_blob_bytes(b"rejected object")?;
+  let accepted = Pointer::from_blob_bytes(b"accepted object")?;
+  repo.lfs_object_store()?.put_bytes(&rejected, b"rejected object")?;
+  repo.lfs_object_store()?.put_bytes(&accepted, b"accepted object")?;
+  std::fs::write(store.join(format!("{}.reject", rejected.hex())), b"")?;
+
+  assert!(client.push(&[rejected, accepted]).await.is_err());
+  assert_eq!(
+    std::fs::read(store.join(accepted.hex()))?,
+    b"accepted object",
+    "a failed upload doesn't stop the batch"
+  );
+
+  Ok(())
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use assert_matches::assert_matches;
+use git2_lfs::remote::reqwest::ReqwestLfsClient;
+use git2_lfs::remote::*;
+use url::Url;
+
+use super::http::response;
+use super::http::serve;
+
+const JSON: &str = "Content-Type: application/vnd.git-lfs+json";
+
+fn batch() -> BatchRequest {
+  BatchRequest {
+    operation: "download".to_string(),
+    transfers: vec!["basic".to_string()],
+    objects: vec![],
+    hash_algo: None,
+    ref_name: None,
+  }
+}
+
+#[tokio::test]
+async fn lfs_error_bodies_become_typed_errors() -> Result<(), anyhow::Error> {
+  let (port, _) = serve(vec![
+    response(
+      "403 Forbidden",
+      &[JSON],
+      r#"{"message": "no push access", "documentation_url": "https://example.com/docs", "request_id": "req-1"}"#,
+    ),
+    response("422 Unprocessable Entity", &[JSON], r#"{"message": "invalid oid"}"#),
+    response("429 Too Many Requests", &["Retry-After: 30"], ""),
+    response("507 Insufficient Storage", &[JSON], r#"{"message": "quota exceeded"}"#),
+    response("501 Not Implemented", &[], "no locking here"),
+    response("500 Internal Server Error", &[JSON], r#"{"message": "boom", "request_id": "req-2"}"#),
+    response("404 Not Found", &[JSON], r#"{"message": "no such repository", "request_id": "req-3"}"#),
+    response("429 Too Many Requests", &["Retry-After: Wed, 21 Oct 2065 07:28:00 GMT"], ""),
+  ]);
+
+  let client = ReqwestLfsClient::new(Url::parse(&format!("http://127.0.0.1:{}/lfs", port))?, None);
+
+  let denied = client.batch(batch()).await.unwrap_err();
+  assert_matches!(&denied, RemoteError::AccessDenied(e) if e.request_id.as_deref() == Some("req-1"));
+  assert_eq!(
+    denied.to_string(),
+    "access denied: no push access (see https://example.com/docs) [request id: req-1]"
+  );
+
+  assert_matches!(client.batch(batch()).await, Err(RemoteError::Validation(e)) if e.message == "invalid oid");
+
+  let before = SystemTime::now();
+  assert_matches!(
+    client.batch(batch()).await,
+    Err(RemoteError::RateLimited { error, reset_at: Some(reset_at) })
+      if error.message == "Too Many Requests" && reset_at >= before + Duration::from_secs(30)
+  );
+
+  assert_matches!(client.batch(batch()).await, Err(RemoteError::InsufficientStorage(e)) if e.message == "quota exceeded");
+  assert_matches!(client.batch(batch()).await, Err(RemoteError::NotImplemented(e)) if e.message == "no locking here");
+  assert_matches!(
+    client.batch(batch()).await,
+    Err(RemoteError::Http { status: 500, error }) if error.message == "boom" && error.request_id.as_deref() == Some("req-2")
+  );
+  assert_matches!(
+    client.batch(batch()).await,
+    Err(RemoteError::NotFound(error)) if error.request_id.as_deref() == Some("req-3")
+  );
+
+  let reset_at = UNIX_EPOCH + Duration::from_secs(3_023_335_680);
+  assert_matches!(client.batch(batch()).await, Err(RemoteError::RateLimited { reset_at: Some(at), .. }) if at == reset_at);
+
+  Ok(())
+}
+
+/// A remote with nothing but the required methods.
+struct BasicOnly;
+
+#[async_trait::async_trait]
+impl LfsRemote for BasicOnly {
+  async fn batch(&self, _: BatchRequest) -> Result<BatchResponse, RemoteError> {
+    Err(RemoteError::EmptyResponse)
+  }
+
+  async fn download(&self, _: &ObjectAction, _: &mut Write) -> Result<git2_lfs::Pointer, RemoteError> {
+    Err(RemoteError::EmptyResponse)
+  }
+
+  async fn upload(&self, _: &ObjectAction, _: &[u8]) -> Result<(), RemoteError> {
+    Err(RemoteError::EmptyResponse)
+  }
+
+  async fn verify(&self, _: &ObjectAction, _: &git2_lfs::Pointer) -> Result<(), RemoteError> {
+    Err(RemoteError::EmptyResponse)
+  }
+}
+
+#[tokio::test]
+async fn lfs_remote_defaults_are_not_implemented() {
+  let lock = LockRequest { path: "a.bin".to_string(), ref_name: None };
+  assert_matches!(BasicOnly.lock(lock).await, Err(RemoteError::NotImplemented(e)) if e.message.contains("locking"));
+
+  let action = ObjectAction {
+    href: "https://example.com".to_string(),
+    header: Default::default(),
+    expires_in: None,
+    expires_at: None,
+    authenticated: false,
+  };
+  assert_matches!(BasicOnly.upload_offset(&action).await, Err(RemoteError::NotImplemented(_)));
+}
@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use assert_matches::assert_matches;
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::fetch::FetchOptions;
+use git2_lfs::fetch::FetchStatus;
+use git2_lfs::remote::LfsClient;
+use git2_lfs::remote::RemoteError;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use super::mock::MockRemote;
+use crate::repo;
+use crate::sandbox;
+
+fn planned(repo: &git2::Repository, options: FetchOptions) -> Result<Vec<String>, anyhow::Error> {
+  let plan = repo.lfs_fetch_plan(&options)?;
+  Ok(plan.objects.iter().map(|o| o.path.to_string_lossy().into_owned()).collect())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_fetch_filters_paths_and_reports_each_object(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let object_dir = repo.path().join("lfs/objects");
+  let sig = repo.signature()?;
+
+  std::fs::create_dir_all(workdir.join("media"))?;
+  std::fs::write(workdir.join("old.bin"), b"old")?;
+  std::fs::write(workdir.join("a.bin"), b"a")?;
+  std::fs::write(workdir.join("media/b.bin"), b"b")?;
+  std::fs::write(workdir.join("media/c.bin"), b"c")?;
+
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let first = repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])?;
+
+  index.remove_path(Path::new("old.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Remove old", &tree, &[&repo.find_commit(first)?])?;
+
+  std::fs::remove_dir_all(&object_dir)?;
+
+  assert_eq!(planned(&repo, FetchOptions::default())?, ["a.bin", "media/b.bin", "media/c.bin"]);
+  assert_eq!(planned(&repo, FetchOptions::default().with_refs(&["HEAD~1"]))?.len(), 4);
+  assert_eq!(planned(&repo, FetchOptions::default().with_recent_commits_days(1))?.len(), 4);
+  assert_eq!(planned(&repo, FetchOptions::default().with_all(true))?.len(), 4);
+
+  let options = FetchOptions::default().with_include(&["media"]).with_exclude(&["media/c*"]);
+  assert_eq!(planned(&repo, options)?, ["media/b.bin"]);
+
+  repo.config()?.set_str("lfs.fetchexclude", "media")?;
+  assert_eq!(planned(&repo, FetchOptions::default())?, ["a.bin"]);
+  repo.config()?.remove("lfs.fetchexclude")?;
+
+  Pointer::from_blob_bytes(b"a")?.write_blob_bytes(&object_dir, b"a")?;
+
+  let plan = repo.lfs_fetch_plan(&FetchOptions::default())?;
+  let client = LfsClient::new(&repo, MockRemote::new(&[b"a", b"b"]))?;
+  let results = client.fetch(&plan).await?;
+
+  assert_eq!(results.len(), 3);
+  let status = |path: &str| &results.iter().find(|r| r.path == Path::new(path)).unwrap().status;
+  assert_matches!(status("a.bin"), FetchStatus::Present);
+  assert_matches!(status("media/b.bin"), FetchStatus::Downloaded);
+  assert_matches!(status("media/c.bin"), FetchStatus::Failed(_));
+
+  assert_eq!(std::fs::read(object_dir.join(Pointer::from_blob_bytes(b"b")?.path()))?, b"b");
+  assert!(!object_dir.join(Pointer::from_blob_bytes(b"c")?.path()).exists());
+
+  repo.branch("topic", &repo.find_commit(first)?, false)?;
+  std::fs::remove_dir_all(&object_dir)?;
+  let plan = repo.lfs_fetch_plan(&FetchOptions::default().with_refs(&["topic"]))?;
+  let remote = MockRemote::new(&[b"a", b"b", b"c", b"old"]);
+  LfsClient::new(&repo, remote.clone())?.fetch(&plan).await?;
+  assert_eq!(remote.batch_refs(), ["refs/heads/topic"], "objects are requested for the fetched ref");
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_pull_fails_when_every_download_attempt_fails(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let pointer = Pointer::from_blob_bytes(b"corrupted in transit")?;
+
+  let client = LfsClient::new(&repo, MockRemote::new(&[b"corrupted in transit"]).with_corrupt_downloads())?;
+  assert_matches!(client.pull(&[pointer]).await, Err(RemoteError::ChecksumMismatch));
+  assert!(!repo.lfs_object_store()?.contains(&pointer)?);
+
+  let client = LfsClient::new(&repo, MockRemote::new(&[b"corrupted in transit"]))?;
+  client.pull(&[pointer]).await?;
+  assert!(repo.lfs_object_store()?.contains(&pointer)?);
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_client_needs_the_repository_storage(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  std::fs::write(repo.path().join("config"), "[lfs\n")?;
+  assert!(LfsClient::new(&repo, MockRemote::new(&[])).is_err(), "there's no other store to fall back to");
+
+  Ok(())
+}
@@ -0,0 +1,127 @@
+use std::path::Path;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::fsck::FsckOptions;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use crate::repo;
+use crate::sandbox;
+
+#[rstest]
+fn lfs_fsck_reports_corrupt_missing_and_unconverted(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+  let object_dir = repo.path().join("lfs/objects");
+
+  let corrupt = Pointer::from_blob_bytes(b"will be corrupted")?;
+  let missing = Pointer::from_blob_bytes(b"will be missing")?;
+  std::fs::write(workdir.join("corrupt.bin"), b"will be corrupted")?;
+  std::fs::write(workdir.join("missing.bin"), b"will be missing")?;
+  std::fs::write(workdir.join("fine.bin"), b"fine")?;
+
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+
+  let raw = repo.blob(b"committed without the filter")?;
+  let mut builder = repo.treebuilder(Some(&tree))?;
+  builder.insert("raw.bin", raw, git2::FileMode::Blob.into())?;
+  let tree = repo.find_tree(builder.write()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])?;
+
+  assert!(repo.lfs_fsck(&FsckOptions::default())?.unconverted.len() == 1);
+
+  std::fs::write(object_dir.join(corrupt.path()), b"garbage")?;
+  std::fs::remove_file(object_dir.join(missing.path()))?;
+
+  let report = repo.lfs_fsck(&FsckOptions::default())?;
+  assert!(!report.is_ok());
+
+  assert_eq!(report.corrupt.len(), 1, "{:?}", report.corrupt);
+  assert_eq!(report.corrupt[0].expected, corrupt);
+  assert_eq!(report.corrupt[0].actual, Pointer::from_blob_bytes(b"garbage")?);
+  assert!(!report.corrupt[0].moved);
+  assert!(object_dir.join(corrupt.path()).exists());
+
+  assert_eq!(report.missing.len(), 1, "{:?}", report.missing);
+  assert_eq!(report.missing[0].pointer, missing);
+  assert_eq!(report.missing[0].path, Path::new("missing.bin"));
+
+  assert_eq!(report.unconverted.len(), 1, "{:?}", report.unconverted);
+  assert_eq!(report.unconverted[0].path, Path::new("raw.bin"));
+  assert_eq!(report.unconverted[0].blob, raw);
+
+  let report = repo.lfs_fsck(&FsckOptions::default().with_move_bad(true))?;
+  assert!(report.corrupt[0].moved);
+  assert!(!object_dir.join(corrupt.path()).exists());
+  assert_eq!(std::fs::read(repo.path().join("lfs/bad").join(corrupt.hex()))?, b"garbage");
+
+  let report = repo.lfs_fsck(&FsckOptions::default())?;
+  assert!(report.corrupt.is_empty());
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_fsck_detects_size_mismatch_and_walks_history(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::write(workdir.join("old.bin"), b"old content")?;
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let first = repo.commit(Some("HEAD"), &sig, &sig, "Add old", &tree, &[])?;
+
+  index.remove_path(Path::new("old.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Remove old", &tree, &[&repo.find_commit(first)?])?;
+
+  let old = Pointer::from_blob_bytes(b"old content")?;
+  std::fs::remove_file(repo.path().join("lfs/objects").join(old.path()))?;
+
+  assert!(repo.lfs_fsck(&FsckOptions::default())?.is_ok());
+
+  let report = repo.lfs_fsck(&FsckOptions::default().with_history(true))?;
+  assert_eq!(report.missing.len(), 1);
+  assert_eq!(report.missing[0].commit, first);
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_fsck_uses_each_commits_attributes(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let sig = repo.signature()?;
+  let raw = repo.blob(b"committed without the filter")?;
+
+  let commit = |attributes: &str, parents: &[&git2::Commit<'_>]| -> Result<git2::Oid, anyhow::Error> {
+    let mut builder = repo.treebuilder(None)?;
+    builder.insert(".gitattributes", repo.blob(attributes.as_bytes())?, git2::FileMode::Blob.into())?;
+    builder.insert("raw.dat", raw, git2::FileMode::Blob.into())?;
+    let tree = repo.find_tree(builder.write()?)?;
+    Ok(repo.commit(Some("HEAD"), &sig, &sig, attributes, &tree, parents)?)
+  };
+
+  let first = commit("*.dat filter=lfs diff=lfs", &[])?;
+  commit("*.bin filter=lfs diff=lfs", &[&repo.find_commit(first)?])?;
+
+  assert!(repo.lfs_fsck(&FsckOptions::default())?.is_ok(), "the tip no longer tracks *.dat");
+
+  let report = repo.lfs_fsck(&FsckOptions::default().with_history(true))?;
+  assert_eq!(report.unconverted.len(), 1, "{:?}", report.unconverted);
+  assert_eq!(report.unconverted[0].path, Path::new("raw.dat"));
+  assert_eq!(report.unconverted[0].commit, first);
+
+  Ok(())
+}
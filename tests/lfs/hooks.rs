@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use assert_matches::assert_matches;
+use git2_lfs::Error;
+use git2_lfs::Pointer;
+use git2_lfs::hooks::PrePush;
+use git2_lfs::remote::LfsClient;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use super::mock::MockRemote;
+use crate::repo;
+use crate::sandbox;
+
+#[rstest]
+#[tokio::test]
+async fn lfs_pre_push_uploads_new_objects(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::write(workdir.join("a.bin"), b"a")?;
+  let mut index = repo.index()?;
+  index.add_path(Path::new("a.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let base = repo.commit(Some("HEAD"), &sig, &sig, "Base", &tree, &[])?;
+  repo.reference("refs/remotes/origin/main", base, true, "fetched")?;
+
+  std::fs::write(workdir.join("b.bin"), b"b")?;
+  index.add_path(Path::new("b.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let head = repo.commit(Some("HEAD"), &sig, &sig, "New", &tree, &[&repo.find_commit(base)?])?;
+
+  let zero = "0".repeat(40);
+  let input =
+    format!("refs/heads/topic {head} refs/heads/topic {zero}\n\n(delete) {zero} refs/heads/old {base}\n");
+  let pre_push = PrePush::parse("origin", "https://example.com/repo", &input)?;
+
+  assert_eq!(pre_push.updates.len(), 2);
+  assert!(pre_push.updates[0].remote_oid.is_zero());
+  assert!(pre_push.updates[1].is_delete());
+  assert_eq!(pre_push.lfs_url().unwrap().as_str(), "https://example.com/repo.git/info/lfs");
+
+  let remote = MockRemote::new(&[]);
+  LfsClient::new(&repo, remote.clone())?.pre_push(&repo, &pre_push).await?;
+
+  assert!(remote.has(&Pointer::from_blob_bytes(b"b")?));
+  assert!(!remote.has(&Pointer::from_blob_bytes(b"a")?), "already on a remote-tracking ref");
+
+  assert_matches!(PrePush::parse("origin", "", "refs/heads/main abc"), Err(Error::InvalidPushUpdate(_)));
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_install_hooks(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let hooks = repo.path().join("hooks");
+  std::fs::create_dir_all(&hooks)?;
+  std::fs::write(hooks.join("post-merge"), "#!/bin/sh\necho custom\n")?;
+
+  assert_matches!(git2_lfs::hooks::install(&repo, false), Err(Error::HookExists(path)) if path.ends_with("post-merge"));
+
+  let installed = git2_lfs::hooks::install(&repo, true)?;
+  assert_eq!(installed.len(), 4);
+
+  for hook in git2_lfs::hooks::HOOKS {
+    let script = std::fs::read_to_string(hooks.join(hook))?;
+    assert!(script.contains(&format!("git lfs {hook} \"$@\"")), "{}", script);
+
+    #[cfg(unix)]
+    {
+      use std::os::unix::fs::PermissionsExt;
+      assert_eq!(std::fs::metadata(hooks.join(hook))?.permissions().mode() & 0o111, 0o111);
+    }
+  }
+
+  assert!(git2_lfs::hooks::install(&repo, false)?.is_empty(), "reinstalling is a no-op");
+
+  repo.config()?.set_str("core.hooksPath", "custom-hooks")?;
+  git2_lfs::hooks::install(&repo, false)?;
+  let custom = repo.workdir().unwrap().join("custom-hooks/pre-push");
+  let script = std::fs::read_to_string(&custom)?;
+  assert!(script.contains(&format!("Remove {} to stop", custom.display())), "{}", script);
+
+  Ok(())
+}
@@ -0,0 +1,46 @@
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read as _;
+use std::io::Write as _;
+use std::net::TcpListener;
+use std::sync::mpsc;
+
+/// Answers one request per response on a local port, in order, handing the lowercased request heads back.
+pub fn serve(responses: Vec<String>) -> (u16, mpsc::Receiver<String>) {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let port = listener.local_addr().unwrap().port();
+  let (tx, rx) = mpsc::channel();
+
+  std::thread::spawn(move || {
+    for (stream, response) in listener.incoming().zip(responses) {
+      let mut stream = stream.unwrap();
+      let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+      let mut head = String::new();
+      while reader.read_line(&mut head).unwrap() > 2 {}
+
+      // Closing the socket with the body unread would reset the connection before the client reads the
+      // response.
+      let head = head.to_lowercase();
+      let length =
+        head.lines().find_map(|l| l.strip_prefix("content-length:")).map_or(0, |l| l.trim().parse().unwrap());
+      reader.read_exact(&mut vec![0; length]).unwrap();
+
+      stream.write_all(response.as_bytes()).unwrap();
+      let _ = tx.send(head);
+    }
+  });
+
+  (port, rx)
+}
+
+pub fn response(status: &str, headers: &[&str], body: &str) -> String {
+  let headers = headers.iter().map(|h| format!("{}\r\n", h)).collect::<String>();
+  format!(
+    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    headers,
+    body.len(),
+    body
+  )
+}
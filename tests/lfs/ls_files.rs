@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::ls_files::LfsFiles;
+use git2_lfs::ls_files::LsFilesSource;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use crate::repo;
+use crate::sandbox;
+
+fn paths(files: LfsFiles<'_>) -> Result<Vec<(String, bool, bool)>, anyhow::Error> {
+  let mut paths = Vec::new();
+  for file in files {
+    let file = file?;
+    paths.push((file.path.to_string_lossy().into_owned(), file.present, file.deleted));
+  }
+
+  paths.sort();
+  Ok(paths)
+}
+
+#[rstest]
+fn lfs_ls_files_in_tree_index_and_diff(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::create_dir_all(workdir.join("dir"))?;
+  std::fs::write(workdir.join("a.bin"), b"a")?;
+  std::fs::write(workdir.join("dir/b.bin"), b"b")?;
+  std::fs::write(workdir.join("readme.txt"), b"not lfs")?;
+
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let first = repo.find_commit(repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])?)?;
+
+  let b = Pointer::from_blob_bytes(b"b")?;
+  std::fs::remove_file(repo.path().join("lfs/objects").join(b.path()))?;
+
+  let files = repo.lfs_ls_files(LsFilesSource::Tree(&tree))?.collect::<Result<Vec<_>, _>>()?;
+  let file = files.iter().find(|f| f.path == Path::new("dir/b.bin")).unwrap();
+  assert_eq!(file.pointer, b);
+  assert_eq!(file.blob, tree.get_path(Path::new("dir/b.bin"))?.id());
+
+  assert_eq!(
+    paths(repo.lfs_ls_files(LsFilesSource::Commit(&first))?)?,
+    [("a.bin".to_string(), true, false), ("dir/b.bin".to_string(), false, false)]
+  );
+
+  index.remove_path(Path::new("a.bin"))?;
+  std::fs::write(workdir.join("c.bin"), b"c")?;
+  index.add_path(Path::new("c.bin"))?;
+
+  assert_eq!(
+    paths(repo.lfs_ls_files(LsFilesSource::Index(&index))?)?,
+    [("c.bin".to_string(), true, false), ("dir/b.bin".to_string(), false, false)]
+  );
+
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let second = repo.find_commit(repo.commit(Some("HEAD"), &sig, &sig, "Second", &tree, &[&first])?)?;
+
+  assert_eq!(
+    paths(repo.lfs_ls_files(LsFilesSource::Diff(&first, &second))?)?,
+    [("a.bin".to_string(), true, true), ("c.bin".to_string(), true, false)]
+  );
+
+  let side = repo.commit(None, &sig, &sig, "Side", &repo.find_tree(first.tree_id())?, &[&first])?;
+  std::fs::write(workdir.join("d.bin"), b"d")?;
+  index.add_path(Path::new("d.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.reference(
+    "refs/heads/side",
+    repo.commit(None, &sig, &sig, "D", &tree, &[&repo.find_commit(side)?])?,
+    true,
+    "side",
+  )?;
+
+  let history = [
+    ("a.bin".to_string(), true, false),
+    ("c.bin".to_string(), true, false),
+    ("dir/b.bin".to_string(), false, false),
+  ];
+  assert_eq!(paths(repo.lfs_ls_files(LsFilesSource::History(&second))?)?, history);
+
+  let mut all = history.to_vec();
+  all.push(("d.bin".to_string(), true, false));
+  all.sort();
+  assert_eq!(paths(repo.lfs_ls_files(LsFilesSource::All)?)?, all);
+
+  let mut lazy = repo.lfs_ls_files(LsFilesSource::All)?;
+  assert!(lazy.next().is_some());
+
+  Ok(())
+}
@@ -0,0 +1,254 @@
+use std::path::Path;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::migrate::MigrateOptions;
+use git2_lfs::remote::LfsClient;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use super::mock::MockRemote;
+use crate::repo;
+use crate::sandbox;
+
+fn blob_at(repo: &git2::Repository, commit: git2::Oid, path: &str) -> Result<Vec<u8>, anyhow::Error> {
+  let entry = repo.find_commit(commit)?.tree()?.get_path(Path::new(path))?;
+  Ok(repo.find_blob(entry.id())?.content().to_vec())
+}
+
+fn commit_all(repo: &git2::Repository, message: &str) -> Result<git2::Oid, anyhow::Error> {
+  let sig = repo.signature()?;
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  index.write()?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let parents = match repo.head() {
+    Ok(head) => vec![head.peel_to_commit()?],
+    Err(_) => vec![],
+  };
+
+  Ok(repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents.iter().collect::<Vec<_>>())?)
+}
+
+#[rstest]
+fn lfs_migrate_import_rewrites_history(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let store = repo.lfs_object_store()?;
+
+  std::fs::create_dir_all(workdir.join("dir"))?;
+  std::fs::write(workdir.join("image.psd"), b"first image")?;
+  std::fs::write(workdir.join("dir/nested.psd"), b"nested image")?;
+  std::fs::write(workdir.join("notes.txt"), b"notes")?;
+  let first = commit_all(&repo, "Initial")?;
+
+  std::fs::write(workdir.join("image.psd"), b"second image")?;
+  let second = commit_all(&repo, "Update image")?;
+
+  let report = repo.lfs_migrate_import(&MigrateOptions::default().with_include(&["*.psd"]))?;
+
+  assert_eq!(report.commits.len(), 2);
+  assert_eq!(report.blobs, 3);
+  assert_eq!(report.refs.len(), 1);
+  assert_eq!(report.refs[0].name, repo.head()?.name().unwrap_or_default());
+  assert_eq!(report.refs[0].old, second);
+
+  let new_second = report.commits[&second];
+  let new_first = report.commits[&first];
+  assert_eq!(repo.head()?.peel_to_commit()?.id(), new_second);
+  assert_eq!(repo.find_commit(new_second)?.parent_id(0)?, new_first);
+  assert_eq!(repo.find_commit(new_second)?.message().unwrap_or_default(), "Update image");
+
+  for (commit, path, content) in [
+    (new_first, "image.psd", &b"first image"[..]),
+    (new_first, "dir/nested.psd", b"nested image"),
+    (new_second, "image.psd", b"second image"),
+  ] {
+    let pointer = Pointer::from_blob_bytes(content)?;
+    assert_eq!(blob_at(&repo, commit, path)?, pointer.as_bytes()?);
+    assert!(store.contains(&pointer)?);
+  }
+
+  assert_eq!(blob_at(&repo, new_second, "notes.txt")?, b"notes");
+
+  let attributes = String::from_utf8(blob_at(&repo, new_first, ".gitattributes")?)?;
+  assert!(attributes.lines().any(|l| l == "*.psd filter=lfs diff=lfs merge=lfs -text"), "{}", attributes);
+  assert!(attributes.contains("*.bin filter=lfs"));
+
+  let report = repo.lfs_migrate_import(&MigrateOptions::default().with_include(&["*.psd"]))?;
+  assert!(report.commits.is_empty());
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_migrate_import_above_size(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+
+  std::fs::write(workdir.join("large file.dat"), vec![7u8; 2048])?;
+  std::fs::write(workdir.join("small.dat"), b"small")?;
+  let first = commit_all(&repo, "Initial")?;
+
+  let report = repo.lfs_migrate_import(&MigrateOptions::default().with_above(1024))?;
+  let new_first = report.commits[&first];
+
+  let pointer = Pointer::from_blob_bytes(&[7u8; 2048])?;
+  assert_eq!(blob_at(&repo, new_first, "large file.dat")?, pointer.as_bytes()?);
+  assert_eq!(blob_at(&repo, new_first, "small.dat")?, b"small");
+
+  let attributes = String::from_utf8(blob_at(&repo, new_first, ".gitattributes")?)?;
+  assert!(attributes.contains("/large[[:space:]]file.dat filter=lfs"), "{}", attributes);
+
+  let statuses = repo.statuses(None)?;
+  assert!(statuses.iter().all(|s| !s.status().is_index_modified()), "index should match the rewritten HEAD");
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_migrate_import_only_touches_converted_commits(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+
+  std::fs::write(workdir.join("notes.txt"), b"notes")?;
+  let first = commit_all(&repo, "Initial")?;
+
+  std::fs::write(workdir.join("#1 [draft]!.psd"), b"image")?;
+  let second = commit_all(&repo, "Add image")?;
+
+  let report = repo.lfs_migrate_import(&MigrateOptions::default().with_include(&["*.psd"]))?;
+  assert!(!report.commits.contains_key(&first), "nothing was converted in the first commit");
+  let new_second = report.commits[&second];
+  assert_eq!(repo.find_commit(new_second)?.parent_id(0)?, first);
+
+  let report = repo.lfs_migrate_export(&MigrateOptions::default())?;
+  let exported = report.commits[&new_second];
+  let report = repo.lfs_migrate_import(&MigrateOptions::default().with_above(1))?;
+  let attributes = String::from_utf8(blob_at(&repo, report.commits[&exported], ".gitattributes")?)?;
+  assert!(attributes.contains("/\\#1[[:space:]]\\[draft]\\!.psd filter=lfs"), "{}", attributes);
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_migrate_refuses_to_run_over_local_changes(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+
+  std::fs::write(workdir.join("image.psd"), b"image")?;
+  let first = commit_all(&repo, "Initial")?;
+
+  std::fs::write(workdir.join("notes.txt"), b"staged")?;
+  let mut index = repo.index()?;
+  index.add_path(Path::new("notes.txt"))?;
+  index.write()?;
+  std::fs::write(workdir.join(".gitattributes"), "*.bin filter=lfs diff=lfs\n*.txt -diff\n")?;
+
+  let options = MigrateOptions::default().with_include(&["*.psd"]);
+  assert!(matches!(repo.lfs_migrate_import(&options), Err(git2_lfs::Error::DirtyWorktree)));
+  assert_eq!(repo.head()?.peel_to_commit()?.id(), first);
+  assert!(repo.index()?.get_path(Path::new("notes.txt"), 0).is_some());
+  assert!(std::fs::read_to_string(workdir.join(".gitattributes"))?.contains("*.txt -diff"));
+
+  commit_all(&repo, "Local changes")?;
+  assert_eq!(repo.lfs_migrate_import(&options)?.refs.len(), 1);
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_migrate_export_inlines_objects(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+
+  std::fs::write(workdir.join("image.psd"), b"image")?;
+  std::fs::write(workdir.join("video.mp4"), b"video")?;
+  commit_all(&repo, "Initial")?;
+
+  let options = MigrateOptions::default().with_include(&["*.psd", "*.mp4"]);
+  let imported = repo.lfs_migrate_import(&options)?.refs[0].new;
+
+  let video = Pointer::from_blob_bytes(b"video")?;
+  repo.lfs_object_store()?.remove(&video)?;
+
+  let options = MigrateOptions::default().with_include(&["*.psd"]);
+  let report = repo.lfs_migrate_export(&options)?;
+  let exported = report.commits[&imported];
+
+  assert_eq!(blob_at(&repo, exported, "image.psd")?, b"image");
+  assert_eq!(blob_at(&repo, exported, "video.mp4")?, video.as_bytes()?);
+
+  let attributes = String::from_utf8(blob_at(&repo, exported, ".gitattributes")?)?;
+  assert!(!attributes.contains("*.psd"), "{}", attributes);
+  assert!(attributes.contains("*.mp4 filter=lfs"), "{}", attributes);
+  assert_eq!(std::fs::read_to_string(workdir.join(".gitattributes"))?, attributes);
+
+  repo.lfs_object_store()?.remove(&video)?;
+  repo.branch("exported", &repo.find_commit(exported)?, false)?;
+  assert!(repo.lfs_migrate_export(&MigrateOptions::default().with_refs(&["exported"])).is_err());
+
+  let client = LfsClient::new(&repo, MockRemote::new(&[b"video"]))?;
+  let report = client.migrate_export(&repo, &MigrateOptions::default()).await?;
+  let exported = report.commits[&exported];
+
+  assert_eq!(blob_at(&repo, exported, "video.mp4")?, b"video");
+  assert!(blob_at(&repo, exported, ".gitattributes").is_err(), "only lfs attributes were left");
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_migrate_info_groups_sizes(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+
+  std::fs::create_dir_all(workdir.join("assets"))?;
+  std::fs::write(workdir.join("assets/large.psd"), vec![1u8; 4096])?;
+  std::fs::write(workdir.join("assets/small.psd"), vec![2u8; 100])?;
+  std::fs::write(workdir.join("tracked.bin"), vec![3u8; 4096])?;
+  std::fs::write(workdir.join("README"), vec![4u8; 2048])?;
+  std::fs::write(workdir.join("assets/README"), vec![6u8; 2048])?;
+  commit_all(&repo, "Initial")?;
+
+  std::fs::write(workdir.join("assets/large.psd"), vec![5u8; 8192])?;
+  commit_all(&repo, "Update")?;
+
+  let info = repo.lfs_migrate_info(&MigrateOptions::default().with_above(1024))?;
+
+  assert_eq!(info.pointers, 1);
+  assert_eq!(info.extensions[0].name, "*.psd");
+  assert_eq!(info.extensions[0].blobs, 3);
+  assert_eq!(info.extensions[0].total_size, 4096 + 100 + 8192);
+  assert_eq!(info.extensions[0].largest_size, 8192);
+  assert_eq!(info.extensions[0].largest_path, "assets/large.psd");
+
+  let assets = info.prefixes.iter().find(|p| p.name == "assets").unwrap();
+  assert_eq!(assets.blobs, 4);
+
+  let readme = info.extensions.iter().find(|e| e.name == "README").unwrap();
+  assert_eq!(readme.blobs, 2);
+  assert_eq!(readme.total_size, 4096);
+
+  assert_eq!(info.recommended_extensions, ["psd"]);
+  assert_eq!(info.recommended_patterns, ["*.psd", "README"]);
+
+  let json = serde_json::to_value(&info)?;
+  assert_eq!(json["recommendedPatterns"][0], "*.psd");
+  assert_eq!(json["extensions"][0]["largestSize"], 8192);
+
+  Ok(())
+}
@@ -0,0 +1,304 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use git2_lfs::Pointer;
+use git2_lfs::remote::*;
+
+/// Keeps objects in memory, shared between clones: downloads of unknown objects fail and uploads of known ones are skipped.
+#[derive(Clone)]
+pub struct MockRemote {
+  objects: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+  transfer: Option<String>,
+  part_size: Option<u64>,
+  parts: Arc<Mutex<HashMap<String, BTreeMap<u64, Vec<u8>>>>>,
+  part_failures: Arc<Mutex<usize>>,
+  upload_failures: Arc<Mutex<usize>>,
+  part_headers: Arc<Mutex<Vec<HashMap<String, String>>>>,
+  want_digest: Option<String>,
+  aborted: Arc<Mutex<Vec<String>>>,
+  partial: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+  offsets: Arc<Mutex<Vec<u64>>>,
+  interrupt_after: Arc<Mutex<Option<usize>>>,
+  refs: Arc<Mutex<Vec<String>>>,
+  authenticated: bool,
+  actions_authenticated: Arc<Mutex<Vec<bool>>>,
+  corrupt: bool,
+}
+
+impl MockRemote {
+  pub fn new(contents: &[&[u8]]) -> Self {
+    let objects = contents
+      .iter()
+      .map(|content| (Pointer::from_blob_bytes(content).unwrap().hex(), content.to_vec()))
+      .collect();
+    Self {
+      objects: Arc::new(Mutex::new(objects)),
+      transfer: None,
+      part_size: None,
+      parts: Default::default(),
+      part_failures: Default::default(),
+      upload_failures: Default::default(),
+      part_headers: Default::default(),
+      want_digest: None,
+      aborted: Default::default(),
+      partial: Default::default(),
+      offsets: Default::default(),
+      interrupt_after: Default::default(),
+      refs: Default::default(),
+      authenticated: false,
+      actions_authenticated: Default::default(),
+      corrupt: false,
+    }
+  }
+
+  /// Picks the `name` adapter whenever a batch request offers it.
+  pub fn with_transfer(self, name: &str) -> Self {
+    Self { transfer: Some(name.to_string()), ..self }
+  }
+
+  /// Hands out `multipart-basic` uploads split into parts of `size` bytes whenever a batch request offers it.
+  pub fn with_parts(self, size: u64) -> Self {
+    Self { part_size: Some(size), ..self.with_transfer("multipart-basic") }
+  }
+
+  /// Serves content that doesn't match the requested object on every download.
+  pub fn with_corrupt_downloads(self) -> Self {
+    Self { corrupt: true, ..self }
+  }
+
+  /// Marks every object of a batch response as authenticated, leaving its actions as they are.
+  pub fn with_authenticated(self) -> Self {
+    Self { authenticated: true, ..self }
+  }
+
+  /// Whether each download and basic upload action was authenticated, in order.
+  pub fn actions_authenticated(&self) -> Vec<bool> {
+    self.actions_authenticated.lock().unwrap().clone()
+  }
+
+  /// Fails the next `count` part uploads, whichever parts they are.
+  pub fn with_part_failures(self, count: usize) -> Self {
+    *self.part_failures.lock().unwrap() = count;
+    self
+  }
+
+  /// Fails the next `count` uploads of whole objects.
+  pub fn with_upload_failures(self, count: usize) -> Self {
+    *self.upload_failures.lock().unwrap() = count;
+    self
+  }
+
+  /// Asks for a digest of every part with `want_digest`.
+  pub fn with_want_digest(self, want_digest: &str) -> Self {
+    Self { want_digest: Some(want_digest.to_string()), ..self }
+  }
+
+  /// Headers of every part upload that arrived, in order.
+  pub fn part_headers(&self) -> Vec<HashMap<String, String>> {
+    self.part_headers.lock().unwrap().clone()
+  }
+
+  /// Oids of the multipart uploads that were aborted.
+  pub fn aborted(&self) -> Vec<String> {
+    self.aborted.lock().unwrap().clone()
+  }
+
+  /// Parts received for an object, committed or not.
+  pub fn parts(&self, pointer: &Pointer) -> usize {
+    self.parts.lock().unwrap().get(&pointer.hex()).map_or(0, |p| p.len())
+  }
+
+  /// Drops the connection of the next resumable upload after `bytes` bytes, keeping what arrived.
+  pub fn with_interruption(self, bytes: usize) -> Self {
+    *self.interrupt_after.lock().unwrap() = Some(bytes);
+    self
+  }
+
+  /// Offsets resumable uploads were sent from, in order.
+  pub fn upload_offsets(&self) -> Vec<u64> {
+    self.offsets.lock().unwrap().clone()
+  }
+
+  /// Refs batch requests were made for, in order.
+  pub fn batch_refs(&self) -> Vec<String> {
+    self.refs.lock().unwrap().clone()
+  }
+
+  pub fn has(&self, pointer: &Pointer) -> bool {
+    self.objects.lock().unwrap().contains_key(&pointer.hex())
+  }
+}
+
+fn action(oid: &str) -> ObjectAction {
+  href(format!("https://example.com/{}", oid))
+}
+
+fn href(href: String) -> ObjectAction {
+  ObjectAction { href, header: Default::default(), expires_in: None, expires_at: None, authenticated: false }
+}
+
+#[async_trait]
+impl LfsRemote for MockRemote {
+  async fn batch(&self, req: BatchRequest) -> Result<BatchResponse, RemoteError> {
+    let upload = req.operation == "upload";
+    self.refs.lock().unwrap().extend(req.ref_name.map(|r| r.name));
+    let transfer = self.transfer.clone().filter(|t| req.transfers.contains(t)).unwrap_or("basic".to_string());
+    let multipart = transfer == "multipart-basic";
+    let objects = req
+      .objects
+      .into_iter()
+      .map(|o| {
+        let known = self.objects.lock().unwrap().contains_key(&o.oid);
+        let actions = match (upload, known) {
+          (true, true) => None,
+          (true, false) if multipart => Some(self.multipart_actions(&o.oid, o.size)),
+          (true, false) => Some(ObjectActions { upload: Some(action(&o.oid)), ..Default::default() }),
+          (false, true) => Some(ObjectActions { download: Some(action(&o.oid)), ..Default::default() }),
+          (false, false) => None,
+        };
+
+        BatchResponseObject {
+          authenticated: self.authenticated.then_some(true),
+          actions,
+          error: (!upload && !known).then(|| ObjectError { code: 404, message: "not found".to_string() }),
+          oid: o.oid,
+          size: o.size,
+        }
+      })
+      .collect();
+
+    Ok(BatchResponse { transfer: Some(transfer), objects, hash_algo: None })
+  }
+
+  async fn download(&self, action: &ObjectAction, to: &mut Write) -> Result<Pointer, RemoteError> {
+    self.actions_authenticated.lock().unwrap().push(action.authenticated);
+    let oid = action.href.rsplit('/').next().unwrap_or_default();
+    let content = self.objects.lock().unwrap().get(oid).cloned();
+    let mut content = content.ok_or_else(|| RemoteError::NotFound(ErrorResponse::new(oid)))?;
+    if self.corrupt {
+      content.reverse();
+      content.push(b'!');
+    }
+
+    to.write_all(&content)?;
+    Ok(Pointer::from_blob_bytes(&content).map_err(|e| RemoteError::Download(e.to_string()))?)
+  }
+
+  async fn upload(&self, action: &ObjectAction, blob: &[u8]) -> Result<(), RemoteError> {
+    if let Some((oid, pos)) =
+      action.href.strip_prefix("https://example.com/").and_then(|p| p.split_once("/parts/"))
+    {
+      let pos = pos.parse().map_err(|_| RemoteError::Upload(action.href.clone()))?;
+
+      let mut failures = self.part_failures.lock().unwrap();
+      if *failures > 0 {
+        *failures -= 1;
+        return Err(RemoteError::Upload("connection reset".to_string()));
+      }
+
+      self.part_headers.lock().unwrap().push(action.header.clone());
+      self.parts.lock().unwrap().entry(oid.to_string()).or_default().insert(pos, blob.to_vec());
+      return Ok(());
+    }
+
+    let mut failures = self.upload_failures.lock().unwrap();
+    if *failures > 0 {
+      *failures -= 1;
+      return Err(RemoteError::Upload("connection reset".to_string()));
+    }
+
+    self.actions_authenticated.lock().unwrap().push(action.authenticated);
+    let oid = action.href.rsplit('/').next().unwrap_or_default();
+    self.objects.lock().unwrap().insert(oid.to_string(), blob.to_vec());
+    Ok(())
+  }
+
+  async fn verify(&self, _: &ObjectAction, _: &Pointer) -> Result<(), RemoteError> {
+    Err(RemoteError::NotFound(ErrorResponse::new("not found")))
+  }
+
+  fn supports_multipart(&self) -> bool {
+    true
+  }
+
+  fn supports_tus(&self) -> bool {
+    true
+  }
+
+  async fn upload_offset(&self, action: &ObjectAction) -> Result<u64, RemoteError> {
+    let oid = action.href.rsplit('/').next().unwrap_or_default();
+    Ok(self.partial.lock().unwrap().get(oid).map_or(0, |p| p.len() as u64))
+  }
+
+  async fn upload_at(&self, action: &ObjectAction, offset: u64, blob: Vec<u8>) -> Result<(), RemoteError> {
+    let oid = action.href.rsplit('/').next().unwrap_or_default();
+    self.offsets.lock().unwrap().push(offset);
+
+    let mut partial = self.partial.lock().unwrap();
+    let received = partial.entry(oid.to_string()).or_default();
+    if received.len() as u64 != offset {
+      return Err(RemoteError::Upload(format!("offset {} doesn't match {}", offset, received.len())));
+    }
+
+    if let Some(bytes) = self.interrupt_after.lock().unwrap().take() {
+      received.extend_from_slice(&blob[..bytes.min(blob.len())]);
+      return Err(RemoteError::Upload("connection reset".to_string()));
+    }
+
+    received.extend_from_slice(&blob);
+    if Pointer::from_blob_bytes(received).is_ok_and(|p| p.hex() == oid) {
+      self.objects.lock().unwrap().insert(oid.to_string(), received.clone());
+    }
+
+    Ok(())
+  }
+
+  /// Joins the parts into the object on commit, checking them against the pointer as a server would.
+  async fn multipart(&self, action: &MultipartAction, pointer: &Pointer) -> Result<(), RemoteError> {
+    let parts = self.parts.lock().unwrap().get(&pointer.hex()).cloned().unwrap_or_default();
+    if !action.action.href.ends_with("/commit") {
+      self.aborted.lock().unwrap().push(pointer.hex());
+      self.parts.lock().unwrap().remove(&pointer.hex());
+      return Ok(());
+    }
+
+    let content = parts.into_values().flatten().collect::<Vec<_>>();
+    if Pointer::from_blob_bytes(&content).ok().as_ref() != Some(pointer) {
+      return Err(RemoteError::ChecksumMismatch);
+    }
+
+    self.objects.lock().unwrap().insert(pointer.hex(), content);
+    Ok(())
+  }
+}
+
+impl MockRemote {
+  fn multipart_actions(&self, oid: &str, size: u64) -> ObjectActions {
+    let part_size = self.part_size.unwrap_or(size).max(1);
+    let parts = (0..size)
+      .step_by(part_size as usize)
+      .map(|pos| PartAction {
+        action: href(format!("https://example.com/{}/parts/{}", oid, pos)),
+        pos,
+        size: (pos + part_size < size).then_some(part_size),
+        want_digest: self.want_digest.clone(),
+      })
+      .collect();
+
+    let multipart = |name: &str| MultipartAction {
+      action: href(format!("https://example.com/{}/{}", oid, name)),
+      method: None,
+      body: None,
+    };
+
+    ObjectActions {
+      parts: Some(parts),
+      commit: Some(multipart("commit")),
+      abort: Some(multipart("abort")),
+      ..Default::default()
+    }
+  }
+}
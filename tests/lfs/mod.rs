@@ -12,8 +12,25 @@ use crate::repo;
 use crate::sandbox;
 
 mod blob;
+mod checkout;
+#[cfg(feature = "cli")]
+mod cli;
+mod credentials;
+#[cfg(unix)]
+mod custom_transfer;
+mod errors;
+mod fetch;
+mod fsck;
+mod hooks;
+mod http;
+mod ls_files;
+mod migrate;
+mod mock;
+mod prune;
 mod pull;
 mod push;
+mod server;
+mod status;
 
 #[rstest]
 fn lfs_ignore_nonlfs_files(
@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::prune::PruneOptions;
+use git2_lfs::remote::LfsClient;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use super::mock::MockRemote;
+use crate::repo;
+use crate::sandbox;
+
+fn signature_days_ago(days: i64) -> Result<git2::Signature<'static>, git2::Error> {
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+  git2::Signature::new("tester", "tester@example.com", &git2::Time::new(now - days * 24 * 60 * 60, 0))
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_prune_keeps_retained_and_unverified_objects(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let object_dir = repo.path().join("lfs/objects");
+  let old_sig = signature_days_ago(30)?;
+  let sig = repo.signature()?;
+
+  let old = Pointer::from_blob_bytes(b"old content")?;
+  let current = Pointer::from_blob_bytes(b"current content")?;
+  let unpushed = Pointer::from_blob_bytes(b"unpushed content")?;
+
+  std::fs::write(workdir.join("old.bin"), b"old content")?;
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let first = repo.find_commit(repo.commit(Some("HEAD"), &old_sig, &old_sig, "Add old", &tree, &[])?)?;
+
+  index.remove_path(Path::new("old.bin"))?;
+  std::fs::write(workdir.join("unpushed.bin"), b"unpushed content")?;
+  index.add_path(Path::new("unpushed.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let feature = repo.commit(None, &old_sig, &old_sig, "Unpushed", &tree, &[&first])?;
+  repo.reference("refs/heads/feature", feature, true, "feature")?;
+
+  index.remove_path(Path::new("unpushed.bin"))?;
+  std::fs::write(workdir.join("current.bin"), b"current content")?;
+  index.add_path(Path::new("current.bin"))?;
+  index.write()?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let head = repo.commit(Some("HEAD"), &sig, &sig, "Replace old", &tree, &[&first])?;
+  repo.reference("refs/remotes/origin/master", head, true, "pushed")?;
+
+  let plan = repo.lfs_prune_plan(&PruneOptions::default())?;
+  assert_eq!(plan.candidates, vec![old]);
+  assert_eq!(plan.bytes(), old.size() as u64);
+
+  let plan = repo.lfs_prune_plan(&PruneOptions::default().with_recent_commits_days(60))?;
+  assert!(plan.candidates.is_empty(), "{:?}", plan.candidates);
+
+  let plan = repo.lfs_prune_plan(&PruneOptions::default().with_dry_run(true))?;
+
+  let client = LfsClient::new(&repo, MockRemote::new(&[]))?;
+  let report = client.prune(&plan).await?;
+  assert!(report.deleted.is_empty());
+  assert_eq!(report.unverified, vec![old]);
+
+  let client =
+    LfsClient::new(&repo, MockRemote::new(&[b"old content", b"current content", b"unpushed content"]))?;
+
+  let report = client.prune(&plan).await?;
+  assert!(report.dry_run);
+  assert_eq!(report.deleted, vec![old]);
+  assert_eq!(report.bytes_reclaimed, old.size() as u64);
+  assert!(object_dir.join(old.path()).exists());
+
+  let plan = repo.lfs_prune_plan(&PruneOptions::default())?;
+  let report = client.prune(&plan).await?;
+  assert_eq!(report.deleted, vec![old]);
+  assert!(!object_dir.join(old.path()).exists());
+  assert!(object_dir.join(current.path()).exists());
+  assert!(object_dir.join(unpushed.path()).exists());
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_prune_keeps_objects_of_linked_worktrees(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let old_sig = signature_days_ago(30)?;
+  let sig = repo.signature()?;
+
+  std::fs::write(workdir.join("old.bin"), b"old content")?;
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let first = repo.commit(Some("HEAD"), &old_sig, &old_sig, "Add old", &tree, &[])?;
+
+  index.remove_path(Path::new("old.bin"))?;
+  index.write()?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let head = repo.commit(Some("HEAD"), &sig, &sig, "Remove old", &tree, &[&repo.find_commit(first)?])?;
+  repo.reference("refs/remotes/origin/master", head, true, "pushed")?;
+
+  let old = Pointer::from_blob_bytes(b"old content")?;
+  let staged = Pointer::from_blob_bytes(b"staged in the linked worktree")?;
+  assert_eq!(repo.lfs_prune_plan(&PruneOptions::default())?.candidates, vec![old]);
+
+  let linked_path = _sandbox.path().join("linked");
+  let linked = git2::Repository::open_from_worktree(&repo.worktree("linked", &linked_path, None)?)?;
+  linked.set_head_detached(first)?;
+
+  std::fs::write(linked_path.join("staged.bin"), b"staged in the linked worktree")?;
+  let mut linked_index = linked.index()?;
+  linked_index.add_path(Path::new("staged.bin"))?;
+  linked_index.write()?;
+  assert!(repo.lfs_object_store()?.contains(&staged)?);
+
+  let plan = repo.lfs_prune_plan(&PruneOptions::default())?;
+  assert!(plan.candidates.is_empty(), "{:?}", plan.candidates);
+
+  let plan = linked.lfs_prune_plan(&PruneOptions::default())?;
+  assert!(plan.candidates.is_empty(), "{:?}", plan.candidates);
+
+  Ok(())
+}
@@ -1,9 +1,15 @@
 use std::path::Path;
 
+use assert_matches::assert_matches;
+use git2_lfs::Pointer;
 use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::push::PushUpdate;
+use git2_lfs::remote::LfsClient;
+use git2_lfs::remote::RemoteError;
 use rstest::rstest;
 use tempfile::TempDir;
 
+use super::mock::MockRemote;
 use crate::repo;
 use crate::sandbox;
 
@@ -93,3 +99,233 @@ fn lfs_find_objects_to_push_multiple_commits(
 
   Ok(())
 }
+
+#[rstest]
+fn lfs_find_objects_to_push_dedups_shared_blobs(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::create_dir_all(workdir.join("assets"))?;
+  std::fs::write(workdir.join("assets/file.bin"), vec![3u8; 300])?;
+  std::fs::write(workdir.join("large.txt"), vec![b'a'; 64 * 1024])?;
+
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let first_id = repo.commit(Some("HEAD"), &sig, &sig, "Add assets", &tree, &[])?;
+  let first_commit = repo.find_commit(first_id)?;
+
+  std::fs::write(workdir.join("README.md"), "readme")?;
+  index.add_path(Path::new("README.md"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Add readme", &tree, &[&first_commit])?;
+
+  let head = repo.head()?;
+  let objects = repo.find_lfs_objects_to_push(&head, None)?;
+
+  assert_eq!(objects.len(), 1, "expected 1 object, got {:?}", objects);
+  assert_eq!(objects[0].size(), 300);
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_push_updates_for_several_refs(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+  let mut index = repo.index()?;
+
+  let mut commit =
+    |path: &str, content: &[u8], parent: Option<git2::Oid>| -> Result<git2::Oid, anyhow::Error> {
+      std::fs::write(workdir.join(path), content)?;
+      index.add_path(Path::new(path))?;
+      let tree = repo.find_tree(index.write_tree()?)?;
+      let parents = parent.map(|p| repo.find_commit(p)).transpose()?;
+      Ok(repo.commit(None, &sig, &sig, path, &tree, &parents.iter().collect::<Vec<_>>())?)
+    };
+
+  let base = commit("base.bin", b"base", None)?;
+  repo.reference("refs/remotes/origin/main", base, true, "fetched")?;
+
+  let main = commit("main.bin", b"main", Some(base))?;
+  let feature = commit("feature.bin", b"feature", Some(base))?;
+  let tagged = commit("tag.bin", b"tag", Some(main))?;
+  let tag = repo.tag_lightweight("v1", &repo.find_object(tagged, None)?, false)?;
+
+  let zero = git2::Oid::from_bytes(&[0; 20])?;
+  let updates = [
+    PushUpdate::new("refs/heads/main", main, "refs/heads/main", base),
+    PushUpdate::new("refs/heads/feature", feature, "refs/heads/feature", zero),
+    PushUpdate::new("refs/tags/v1", tag, "refs/tags/v1", zero),
+    PushUpdate::new("(delete)", zero, "refs/heads/old", base),
+  ];
+
+  let mut objects = repo.find_lfs_objects_to_push_for(&updates)?;
+  objects.sort_by_key(|p| p.size());
+  let expected = [&b"tag"[..], b"main", b"feature"].map(|c| Pointer::from_blob_bytes(c).unwrap());
+  assert_eq!(objects, expected);
+
+  assert!(repo.find_lfs_objects_to_push_for(&updates[3..])?.is_empty(), "deletes push nothing");
+
+  let remote = MockRemote::new(&[b"main"]);
+  LfsClient::new(&repo, remote.clone())?.push_updates(&repo, &updates).await?;
+  assert!(expected.iter().all(|p| remote.has(p)));
+  assert_eq!(remote.batch_refs(), ["refs/heads/main", "refs/heads/feature", "refs/tags/v1"]);
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_push_multipart(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let content = b"multipart upload content";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_parts(10);
+  LfsClient::new(&repo, remote.clone())?.part_concurrency_limit(2).push(&[pointer]).await?;
+
+  assert_eq!(remote.parts(&pointer), 3);
+  assert!(remote.has(&pointer));
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_push_retries_uploads_and_fails_once_attempts_run_out(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let content = b"basic upload content";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_upload_failures(3);
+  assert_matches!(LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await, Err(RemoteError::Upload(_)));
+  assert!(!remote.has(&pointer));
+
+  let remote = MockRemote::new(&[]).with_upload_failures(2);
+  LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await?;
+  assert!(remote.has(&pointer), "an upload is retried after it fails");
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_push_multipart_retries_parts_and_aborts(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let content = b"multipart upload content";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_parts(10).with_part_failures(2);
+  LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await?;
+  assert!(remote.has(&pointer), "a part is retried after it fails");
+  assert!(remote.aborted().is_empty());
+
+  let content = b"another multipart upload";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_parts(10).with_part_failures(3);
+  let client = LfsClient::new(&repo, remote.clone())?.part_concurrency_limit(1);
+  assert!(client.push(&[pointer]).await.is_err());
+  assert!(!remote.has(&pointer));
+  assert_eq!(remote.aborted(), [pointer.hex()], "a part that fails every attempt aborts the upload");
+  assert_eq!(remote.parts(&pointer), 0);
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_push_multipart_sends_wanted_digests(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  use base64::Engine as _;
+  use sha2::Digest as _;
+
+  let content = b"digested";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_parts(100).with_want_digest("md5;q=0.3, sha-256;q=0.8, unixsum");
+  LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await?;
+
+  let digest = base64::engine::general_purpose::STANDARD.encode(sha2::Sha256::digest(content));
+  assert_eq!(remote.part_headers()[0]["Digest"], format!("SHA-256={}", digest));
+
+  let content = b"content md5";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_parts(100).with_want_digest("contentMD5");
+  LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await?;
+  assert!(remote.part_headers()[0].contains_key("Content-MD5"));
+
+  let remote = MockRemote::new(&[]).with_parts(100).with_want_digest("unixsum");
+  assert!(LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await.is_err());
+
+  Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_push_tus_resumes(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let content = b"resumable upload content";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let remote = MockRemote::new(&[]).with_transfer("tus").with_interruption(10);
+  LfsClient::new(&repo, remote.clone())?.push(&[pointer]).await?;
+
+  assert_eq!(remote.upload_offsets(), vec![0, 10]);
+  assert!(remote.has(&pointer));
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_find_objects_to_push_skips_objects_of_remote_tracking_parents(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+  let mut index = repo.index()?;
+
+  std::fs::write(workdir.join("fetched.bin"), b"fetched")?;
+  index.add_path(Path::new("fetched.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let fetched = repo.commit(None, &sig, &sig, "Fetched", &tree, &[])?;
+  repo.reference("refs/remotes/origin/main", fetched, true, "fetched")?;
+
+  std::fs::write(workdir.join("topic.bin"), b"topic")?;
+  index.add_path(Path::new("topic.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let topic = repo.commit(None, &sig, &sig, "Topic", &tree, &[&repo.find_commit(fetched)?])?;
+
+  let zero = git2::Oid::from_bytes(&[0; 20])?;
+  let updates = [PushUpdate::new("refs/heads/topic", topic, "refs/heads/topic", zero)];
+  assert_eq!(repo.find_lfs_objects_to_push_for(&updates)?, [Pointer::from_blob_bytes(b"topic")?]);
+
+  Ok(())
+}
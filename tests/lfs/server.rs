@@ -0,0 +1,196 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use async_trait::async_trait;
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::remote::*;
+use git2_lfs::server::Access;
+use git2_lfs::server::LfsServer;
+use git2_lfs::server::ServerError;
+use git2_lfs::store::MemoryObjectStore;
+use git2_lfs::store::ObjectStore;
+use rstest::rstest;
+use tempfile::TempDir;
+use url::Url;
+
+use crate::repo;
+use crate::sandbox;
+
+const ENDPOINT: &str = "https://example.com/repo.git/info/lfs";
+
+/// Routes client requests straight to the server's handlers, as an http framework would.
+struct ServerRemote(Arc<LfsServer>);
+
+fn route(href: &str) -> (String, String) {
+  let url = Url::parse(href).unwrap();
+  let path = url.path().strip_prefix("/repo.git/info/lfs/objects/").unwrap_or_default();
+  let oid = path.trim_end_matches("/verify").to_string();
+  (oid, url.query().unwrap_or_default().to_string())
+}
+
+fn remote_error(e: ServerError) -> RemoteError {
+  match e.status() {
+    404 => RemoteError::NotFound(ErrorResponse::new(e.to_string())),
+    401 | 403 => RemoteError::AccessDenied(ErrorResponse {
+      message: e.to_string(),
+      documentation_url: None,
+      request_id: None,
+    }),
+    _ => RemoteError::Custom(Box::new(e)),
+  }
+}
+
+#[async_trait]
+impl LfsRemote for ServerRemote {
+  async fn batch(&self, req: BatchRequest) -> Result<BatchResponse, RemoteError> {
+    self.0.batch(Some("Bearer alice"), req).map_err(remote_error)
+  }
+
+  async fn download(&self, action: &ObjectAction, to: &mut Write) -> Result<Pointer, RemoteError> {
+    let (oid, query) = route(&action.href);
+    let mut content = Vec::new();
+    self.0.download(&oid, &query).map_err(remote_error)?.read_to_end(&mut content)?;
+    to.write_all(&content)?;
+    Ok(Pointer::from_blob_bytes(&content)?)
+  }
+
+  async fn upload(&self, action: &ObjectAction, mut blob: &[u8]) -> Result<(), RemoteError> {
+    let (oid, query) = route(&action.href);
+    self.0.upload(&oid, &query, &mut blob).map_err(remote_error)
+  }
+
+  async fn verify(&self, action: &ObjectAction, pointer: &Pointer) -> Result<(), RemoteError> {
+    let (oid, query) = route(&action.href);
+    let req = BatchObject { oid: pointer.hex(), size: pointer.size() as u64 };
+    self.0.verify(&oid, &query, req).map_err(remote_error)
+  }
+}
+
+fn server() -> LfsServer {
+  LfsServer::new(Url::parse(ENDPOINT).unwrap(), Arc::new(MemoryObjectStore::new()), b"secret").auth(
+    |authorization: Option<&str>, access: Access, ref_name: Option<&str>| match authorization
+      .and_then(|a| a.strip_prefix("Bearer "))
+    {
+      Some("guest") if access == Access::Upload => Err(ServerError::Forbidden),
+      Some(user) if access == Access::Upload && ref_name == Some("refs/heads/release") && user != "alice" => {
+        Err(ServerError::Forbidden)
+      }
+      Some(user) => Ok(user.to_string()),
+      None => Err(ServerError::Unauthorized),
+    },
+  )
+}
+
+#[rstest]
+#[tokio::test]
+async fn lfs_server_push_and_pull(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let content = b"served by the lfs server";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  repo.lfs_object_store()?.put_bytes(&pointer, content)?;
+
+  let server = Arc::new(server());
+  LfsClient::new(&repo, ServerRemote(server.clone()))?.push(&[pointer]).await?;
+
+  let store = Arc::new(MemoryObjectStore::new());
+  LfsClient::new(&repo, ServerRemote(server.clone()))?.object_store(store.clone()).pull(&[pointer]).await?;
+  assert_eq!(store.read_to_vec(&pointer)?, content);
+
+  let req = |operation: &str| BatchRequest {
+    operation: operation.to_string(),
+    transfers: vec!["basic".to_string()],
+    objects: vec![BatchObject { oid: pointer.hex(), size: pointer.size() as u64 }],
+    hash_algo: None,
+    ref_name: Some(Ref::new("refs/heads/release")),
+  };
+
+  let res = server.batch(Some("Bearer alice"), req("upload"))?;
+  assert!(res.objects[0].actions.is_none(), "the server already has the object");
+
+  assert_matches!(server.batch(Some("Bearer guest"), req("upload")), Err(ServerError::Forbidden));
+  assert_matches!(server.batch(Some("Bearer bob"), req("upload")), Err(ServerError::Forbidden));
+  assert!(server.batch(Some("Bearer bob"), req("download")).is_ok());
+  assert_matches!(server.batch(None, req("download")), Err(e) if e.status() == 401);
+
+  let tus_only = BatchRequest { transfers: vec!["tus".to_string()], ..req("download") };
+  assert_matches!(server.batch(Some("Bearer bob"), tus_only), Err(e @ ServerError::UnsupportedTransfer(_)) if e.status() == 422);
+  let res = server.batch(Some("Bearer bob"), BatchRequest { transfers: vec![], ..req("download") })?;
+  assert_eq!(res.transfer.as_deref(), Some("basic"), "no adapters listed means basic");
+
+  Ok(())
+}
+
+#[test]
+fn lfs_server_rejects_tampered_and_expired_hrefs() -> Result<(), anyhow::Error> {
+  let content = b"signed";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  let req = BatchRequest {
+    operation: "upload".to_string(),
+    transfers: vec![],
+    objects: vec![BatchObject { oid: pointer.hex(), size: pointer.size() as u64 }],
+    hash_algo: Some("sha256".to_string()),
+    ref_name: None,
+  };
+
+  let server = server();
+  let res = server.batch(Some("Bearer alice"), req)?;
+  let (oid, query) = route(&res.objects[0].actions.as_ref().unwrap().upload.as_ref().unwrap().href);
+
+  let tampered = query.replace(&format!("size={}", content.len()), "size=1");
+  assert_matches!(server.upload(&oid, &tampered, &mut &content[..]), Err(ServerError::InvalidSignature));
+  assert_matches!(server.upload(&oid, &query, &mut &b"other"[..]), Err(e) if e.status() == 422);
+  server.upload(&oid, &query, &mut &content[..])?;
+
+  let server = server.expiry(Duration::ZERO);
+  let req = BatchRequest {
+    operation: "download".to_string(),
+    transfers: vec![],
+    objects: vec![BatchObject { oid: pointer.hex(), size: pointer.size() as u64 }],
+    hash_algo: None,
+    ref_name: None,
+  };
+  let res = server.batch(Some("Bearer alice"), req)?;
+  let (oid, query) = route(&res.objects[0].actions.as_ref().unwrap().download.as_ref().unwrap().href);
+  assert_matches!(server.download(&oid, &query).err(), Some(ServerError::InvalidSignature));
+
+  Ok(())
+}
+
+#[test]
+fn lfs_server_locks() -> Result<(), anyhow::Error> {
+  let server = server();
+  let alice = Some("Bearer alice");
+  let bob = Some("Bearer bob");
+
+  let lock = |path: &str| LockRequest { path: path.to_string(), ref_name: None };
+  let a = server.create_lock(alice, lock("a.bin"))?.lock;
+  server.create_lock(bob, lock("b.bin"))?;
+
+  let conflict = server.create_lock(bob, lock("a.bin")).unwrap_err();
+  assert_eq!(conflict.status(), 409);
+  assert_eq!(conflict.body()["lock"]["id"], a.id);
+
+  let listed = server.list_locks(bob, &LockListRequest { limit: Some(1), ..Default::default() })?;
+  assert_eq!(listed.locks, vec![a.clone()]);
+  assert_eq!(listed.next_cursor.as_deref(), Some("1"));
+
+  let filtered =
+    server.list_locks(bob, &LockListRequest { path: Some("b.bin".to_string()), ..Default::default() })?;
+  assert_eq!(filtered.locks.len(), 1);
+  assert_eq!(filtered.locks[0].owner.name, "bob");
+
+  let verified = server.verify_locks(alice, VerifyLocksRequest::default())?;
+  assert_eq!(verified.ours, vec![a.clone()]);
+  assert_eq!(verified.theirs.len(), 1);
+
+  assert_matches!(server.unlock(bob, &a.id, UnlockRequest::default()), Err(ServerError::Forbidden));
+  let forced = UnlockRequest { force: Some(true), ..Default::default() };
+  assert_eq!(server.unlock(bob, &a.id, forced)?.lock, a);
+  assert_matches!(server.unlock(alice, &a.id, UnlockRequest::default()), Err(ServerError::NotFound));
+
+  Ok(())
+}
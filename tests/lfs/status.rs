@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use git2_lfs::Pointer;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::status::StagedChange;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use crate::repo;
+use crate::sandbox;
+
+#[rstest]
+fn lfs_status_reports_staged_unpushed_missing_and_unconverted(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::write(workdir.join("a.bin"), b"a")?;
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let pushed = repo.commit(Some("HEAD"), &sig, &sig, "Pushed", &tree, &[])?;
+
+  let branch = repo.head()?.shorthand().unwrap_or_default().to_string();
+  repo.reference(&format!("refs/remotes/origin/{}", branch), pushed, true, "pushed")?;
+  repo.remote("origin", "https://example.com/repo.git")?;
+  repo.config()?.set_str(&format!("branch.{}.remote", branch), "origin")?;
+  repo.config()?.set_str(&format!("branch.{}.merge", branch), &format!("refs/heads/{}", branch))?;
+
+  std::fs::write(workdir.join("b.bin"), b"b")?;
+  index.add_path(Path::new("b.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Unpushed", &tree, &[&repo.find_commit(pushed)?])?;
+
+  std::fs::write(workdir.join("a.bin"), b"a, modified")?;
+  std::fs::write(workdir.join("c.bin"), b"c")?;
+  index.add_path(Path::new("a.bin"))?;
+  index.add_path(Path::new("c.bin"))?;
+  index.remove_path(Path::new("b.bin"))?;
+
+  let raw = git2::IndexEntry {
+    ctime: git2::IndexTime::new(0, 0),
+    mtime: git2::IndexTime::new(0, 0),
+    dev: 0,
+    ino: 0,
+    mode: 0o100644,
+    uid: 0,
+    gid: 0,
+    file_size: 0,
+    id: git2::Oid::zero(),
+    flags: 0,
+    flags_extended: 0,
+    path: b"raw.bin".to_vec(),
+  };
+  index.add_frombuffer(&raw, b"committed without the filter")?;
+  index.write()?;
+
+  let c = Pointer::from_blob_bytes(b"c")?;
+  repo.lfs_object_store()?.remove(&c)?;
+
+  let status = repo.lfs_status()?;
+
+  let mut staged = status.staged.iter().map(|s| (s.path.to_str().unwrap(), s.change)).collect::<Vec<_>>();
+  staged.sort_by_key(|(path, _)| *path);
+  assert_eq!(
+    staged,
+    [("a.bin", StagedChange::Modified), ("b.bin", StagedChange::Deleted), ("c.bin", StagedChange::Added)]
+  );
+
+  assert_eq!(status.unpushed, [Pointer::from_blob_bytes(b"b")?]);
+
+  assert_eq!(status.missing.len(), 1);
+  assert_eq!(status.missing[0].path, Path::new("c.bin"));
+  assert_eq!(status.missing[0].pointer, c);
+
+  assert_eq!(status.unconverted, [Path::new("raw.bin")]);
+
+  let json = serde_json::to_value(&status)?;
+  assert_eq!(json["missing"][0]["pointer"]["oid"], c.hex());
+  assert_eq!(json["staged"][0]["change"].as_str().map(|c| c.is_empty()), Some(false));
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_status_without_upstream_skips_remote_tracking_history(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let sig = repo.signature()?;
+
+  std::fs::write(workdir.join("a.bin"), b"a")?;
+  let mut index = repo.index()?;
+  index.add_path(Path::new("a.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let fetched = repo.commit(Some("HEAD"), &sig, &sig, "Fetched", &tree, &[])?;
+  repo.reference("refs/remotes/origin/main", fetched, true, "fetched")?;
+
+  std::fs::write(workdir.join("b.bin"), b"b")?;
+  index.add_path(Path::new("b.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Local", &tree, &[&repo.find_commit(fetched)?])?;
+
+  assert_eq!(repo.lfs_status()?.unpushed, [Pointer::from_blob_bytes(b"b")?]);
+
+  Ok(())
+}
@@ -7,6 +7,7 @@ use tempfile::TempDir;
 
 mod lfs;
 mod pointer;
+mod store;
 
 #[fixture]
 pub fn repo(#[default(&sandbox())] sandbox: &TempDir) -> git2::Repository {
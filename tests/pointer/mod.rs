@@ -2,12 +2,18 @@ mod parse;
 
 use std::str::FromStr;
 
+use assert_matches::assert_matches;
+use git2_lfs::Error;
 use git2_lfs::Pointer;
 use rstest::rstest;
+use tempfile::TempDir;
 
+use assertables::assert_err;
 use assertables::assert_ok;
 use sha2::Digest;
 
+use crate::sandbox;
+
 #[rstest]
 fn write_and_parse() -> Result<(), anyhow::Error> {
   let pointer = Pointer::from_blob_bytes(b"blob")?;
@@ -55,3 +61,55 @@ fn is_pointer() -> Result<(), anyhow::Error> {
 
   Ok(())
 }
+
+#[rstest]
+fn write_blob_bytes_is_idempotent_and_leaves_no_temp_files(sandbox: TempDir) -> Result<(), anyhow::Error> {
+  let object_dir = sandbox.path().join("objects");
+  let pointer = Pointer::from_blob_bytes(b"blob")?;
+
+  pointer.write_blob_bytes(&object_dir, b"blob")?;
+  pointer.write_blob_bytes(&object_dir, b"blob")?;
+
+  let object_path = object_dir.join(pointer.path());
+  assert_eq!(std::fs::read(&object_path)?, b"blob");
+  assert_eq!(std::fs::read_dir(object_path.parent().unwrap())?.count(), 1);
+
+  Ok(())
+}
+
+#[rstest]
+fn write_blob_bytes_rejects_mismatched_content(sandbox: TempDir) -> Result<(), anyhow::Error> {
+  let object_dir = sandbox.path().join("objects");
+  let pointer = Pointer::from_blob_bytes(b"blob")?;
+
+  let err = assert_err!(pointer.write_blob_bytes(&object_dir, b"truncated"));
+  assert_matches!(err, Error::ObjectMismatch { .. });
+
+  let object_path = object_dir.join(pointer.path());
+  assert!(!object_path.exists());
+  assert_eq!(std::fs::read_dir(object_path.parent().unwrap())?.count(), 0);
+
+  Ok(())
+}
+
+#[rstest]
+fn write_blob_bytes_concurrent_writers(sandbox: TempDir) -> Result<(), anyhow::Error> {
+  let object_dir = sandbox.path().join("objects");
+  let content = vec![7u8; 256 * 1024];
+  let pointer = Pointer::from_blob_bytes(&content)?;
+
+  std::thread::scope(|scope| {
+    let handles =
+      (0..8).map(|_| scope.spawn(|| pointer.write_blob_bytes(&object_dir, &content))).collect::<Vec<_>>();
+
+    for handle in handles {
+      assert_ok!(handle.join().unwrap());
+    }
+  });
+
+  let object_path = object_dir.join(pointer.path());
+  assert_eq!(std::fs::read(&object_path)?, content);
+  assert_eq!(std::fs::read_dir(object_path.parent().unwrap())?.count(), 1);
+
+  Ok(())
+}
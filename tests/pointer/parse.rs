@@ -3,6 +3,7 @@ use std::str::FromStr;
 use assertables::assert_ok;
 use git2_lfs::Error;
 use git2_lfs::Pointer;
+use git2_lfs::PointerCheck;
 use rstest::rstest;
 
 use assert_matches::assert_matches;
@@ -69,3 +70,22 @@ oid sha256:e69de29bb2d7d028ab54a17b17c1b611b022acc8755b950963d6444464ef4c44
 
   Ok(())
 }
+
+#[rstest]
+fn pointer_check_compares_with_file() -> Result<(), anyhow::Error> {
+  let file = Pointer::from_blob_bytes(b"content")?;
+  let other = Pointer::from_blob_bytes(b"other")?;
+
+  assert_eq!(PointerCheck::new(Some(&file), &file.as_bytes()?, true), PointerCheck::Match(file));
+  assert_eq!(
+    PointerCheck::new(Some(&file), &other.as_bytes()?, false),
+    PointerCheck::Mismatch { expected: file, actual: other }
+  );
+  assert_matches!(PointerCheck::new(None, b"content", false), PointerCheck::Invalid(_));
+
+  let loose = format!("{}\n\n", std::str::from_utf8(&file.as_bytes()?)?);
+  assert!(PointerCheck::new(Some(&file), loose.as_bytes(), false).is_match());
+  assert_matches!(Pointer::check(loose.as_bytes(), true), Err(Error::NonCanonicalPointer));
+
+  Ok(())
+}
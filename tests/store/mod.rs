@@ -0,0 +1,234 @@
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use assertables::assert_ok;
+use git2_lfs::LfsBuilder;
+use git2_lfs::Pointer;
+use git2_lfs::ext::LfsRepository;
+use git2_lfs::ext::RepoLfsExt;
+use git2_lfs::store::FsObjectStore;
+use git2_lfs::store::MemoryObjectStore;
+use git2_lfs::store::ObjectStore;
+use rstest::rstest;
+use tempfile::TempDir;
+
+use crate::repo;
+use crate::sandbox;
+
+fn assert_roundtrip(store: &dyn ObjectStore) -> Result<(), anyhow::Error> {
+  let content = b"object store content";
+  let pointer = Pointer::from_blob_bytes(content)?;
+
+  assert!(!store.contains(&pointer)?);
+  assert_eq!(store.iter()?.count(), 0);
+
+  store.put_bytes(&pointer, content)?;
+  assert!(store.contains(&pointer)?);
+
+  let mut read = Vec::new();
+  store.open(&pointer)?.read_to_end(&mut read)?;
+  assert_eq!(read, content);
+
+  let listed = store.iter()?.collect::<Result<Vec<_>, _>>()?;
+  assert_eq!(listed, vec![pointer]);
+
+  store.remove(&pointer)?;
+  assert!(!store.contains(&pointer)?);
+  assert_ok!(store.remove(&pointer));
+
+  Ok(())
+}
+
+#[rstest]
+fn fs_store_roundtrip(sandbox: TempDir) -> Result<(), anyhow::Error> {
+  let store = FsObjectStore::new(sandbox.path().join("objects"));
+  assert_roundtrip(&store)?;
+
+  std::fs::write(sandbox.path().join("objects/stray"), "not an object")?;
+  assert_eq!(store.iter()?.count(), 0);
+
+  Ok(())
+}
+
+#[rstest]
+fn memory_store_roundtrip() -> Result<(), anyhow::Error> {
+  assert_roundtrip(&MemoryObjectStore::new())
+}
+
+#[rstest]
+fn lfs_repository_uses_custom_store(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let content = b"stored elsewhere";
+  let pointer = Pointer::from_blob_bytes(content)?;
+
+  std::fs::write(workdir.join("file.bin"), content)?;
+
+  let mut index = repo.index()?;
+  index.add_path(Path::new("file.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+
+  let store = MemoryObjectStore::new();
+  let lfs_repo = LfsRepository::new(&repo, Arc::new(store.clone()));
+
+  assert_eq!(lfs_repo.find_tree_missing_lfs_objects(&tree)?, vec![pointer]);
+  assert!(repo.find_tree_missing_lfs_objects(&tree)?.is_empty());
+
+  store.put_bytes(&pointer, content)?;
+  assert!(lfs_repo.find_tree_missing_lfs_objects(&tree)?.is_empty());
+
+  let blob = repo.find_blob(tree.get_path(Path::new("file.bin"))?.id())?;
+  assert_eq!(lfs_repo.get_lfs_blob_content(&blob)?.as_ref(), content);
+
+  Ok(())
+}
+
+#[rstest]
+#[case::absolute(true)]
+#[case::relative(false)]
+fn lfs_storage_from_config(
+  #[case] absolute: bool,
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let storage = if absolute { _sandbox.path().join("shared-lfs") } else { repo.path().join("custom-lfs") };
+  let configured = if absolute { storage.to_str().unwrap() } else { "custom-lfs" };
+  repo.config()?.set_str("lfs.storage", configured)?;
+
+  let content = b"configured storage";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  std::fs::write(repo.workdir().unwrap().join("file.bin"), content)?;
+
+  let mut index = repo.index()?;
+  index.add_path(Path::new("file.bin"))?;
+
+  assert!(storage.join("objects").join(pointer.path()).exists());
+  assert!(!repo.path().join("lfs/objects").join(pointer.path()).exists());
+  assert!(repo.lfs_object_store()?.contains(&pointer)?);
+
+  Ok(())
+}
+
+#[rstest]
+fn linked_worktree_shares_objects(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let content = b"shared between worktrees";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  std::fs::write(repo.workdir().unwrap().join("file.bin"), content)?;
+
+  let mut index = repo.index()?;
+  index.add_all(["*"], git2::IndexAddOption::default(), None)?;
+  index.write()?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+  let sig = repo.signature()?;
+  repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])?;
+
+  let worktree_path = _sandbox.path().join("linked");
+  let worktree = repo.worktree("linked", &worktree_path, None)?;
+  let linked = git2::Repository::open_from_worktree(&worktree)?;
+
+  assert_ne!(linked.path(), repo.path());
+  assert!(linked.find_tree_missing_lfs_objects(&tree)?.is_empty());
+  assert!(linked.lfs_object_store()?.contains(&pointer)?);
+  assert!(!linked.path().join("lfs").exists());
+
+  assert_eq!(std::fs::read(worktree_path.join("file.bin"))?, content);
+
+  Ok(())
+}
+
+#[rstest]
+fn fs_store_materializes_from_alternate(sandbox: TempDir) -> Result<(), anyhow::Error> {
+  let content = b"warm cache";
+  let pointer = Pointer::from_blob_bytes(content)?;
+
+  let alternate = FsObjectStore::new(sandbox.path().join("cache"));
+  alternate.put_bytes(&pointer, content)?;
+
+  let store =
+    FsObjectStore::new(sandbox.path().join("objects")).with_alternates([alternate.root().to_path_buf()]);
+
+  assert!(store.contains(&pointer)?);
+  assert!(!store.object_path(&pointer).exists());
+  assert_eq!(store.iter()?.count(), 0);
+
+  assert_eq!(store.read_to_vec(&pointer)?, content);
+  assert!(store.object_path(&pointer).exists());
+  assert!(alternate.contains(&pointer)?);
+
+  Ok(())
+}
+
+#[rstest]
+fn fs_store_rejects_corrupt_alternate_copies(sandbox: TempDir) -> Result<(), anyhow::Error> {
+  use std::os::unix::fs::MetadataExt;
+
+  // Objects are only copied when they can't be hardlinked, so the cache has to be on another filesystem.
+  let Ok(cache) = tempfile::tempdir_in("/dev/shm") else { return Ok(()) };
+  if std::fs::metadata(cache.path())?.dev() == std::fs::metadata(sandbox.path())?.dev() {
+    return Ok(());
+  }
+
+  let pointer = Pointer::from_blob_bytes(b"shared object")?;
+  let alternate = FsObjectStore::new(cache.path());
+  alternate.put_bytes(&pointer, b"shared object")?;
+  std::fs::write(alternate.object_path(&pointer), b"shared objecT")?;
+
+  let store =
+    FsObjectStore::new(sandbox.path().join("objects")).with_alternates([cache.path().to_path_buf()]);
+  assert_matches!(store.open(&pointer).map(|_| ()), Err(git2_lfs::Error::ObjectMismatch { .. }));
+  assert!(!store.object_path(&pointer).exists(), "the corrupt copy was kept");
+
+  std::fs::write(alternate.object_path(&pointer), b"shared object")?;
+  assert_eq!(store.read_to_vec(&pointer)?, b"shared object");
+
+  Ok(())
+}
+
+#[rstest]
+fn lfs_alternates_from_config(
+  _sandbox: TempDir,
+  #[with(&_sandbox)] repo: git2::Repository,
+) -> Result<(), anyhow::Error> {
+  let workdir = repo.workdir().unwrap();
+  let content = b"from the reference store";
+  let pointer = Pointer::from_blob_bytes(content)?;
+  std::fs::write(workdir.join("file.bin"), content)?;
+
+  let mut index = repo.index()?;
+  index.add_path(Path::new("file.bin"))?;
+  let tree = repo.find_tree(index.write_tree()?)?;
+
+  let alternate = _sandbox.path().join("reference");
+  let object_path = repo.path().join("lfs/objects").join(pointer.path());
+  std::fs::create_dir_all(alternate.join(pointer.path()).parent().unwrap())?;
+  std::fs::rename(&object_path, alternate.join(pointer.path()))?;
+
+  assert_eq!(repo.find_tree_missing_lfs_objects(&tree)?, vec![pointer]);
+
+  repo.config()?.set_multivar("lfs.alternates", "^$", alternate.to_str().unwrap())?;
+
+  assert!(repo.find_tree_missing_lfs_objects(&tree)?.is_empty());
+
+  let blob = repo.find_blob(tree.get_path(Path::new("file.bin"))?.id())?;
+  assert_eq!(repo.get_lfs_blob_content(&blob)?.as_ref(), content);
+  assert!(object_path.exists());
+
+  Ok(())
+}
+
+#[test]
+fn lfs_builder_rejects_alternates_with_custom_store() {
+  let builder = LfsBuilder::default()
+    .with_object_store(MemoryObjectStore::new())
+    .with_alternates(&[Path::new("/tmp/a")])
+    .with_alternates(&[Path::new("/tmp/b")]);
+
+  assert_matches!(builder.install("lfs"), Err(git2_lfs::Error::AlternatesWithCustomStore));
+}